@@ -3,11 +3,18 @@ use console::style;
 use serde::{Deserialize, Serialize};
 use whitenoise::{Account, AccountSettings, Metadata, Whitenoise};
 
-use crate::storage::Storage;
+use crate::{errors::CliError, identifiers, storage::Storage};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct AccountData {
     pub pubkey: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub current: bool,
+    /// Unread messages across the account's groups, when requested
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub unread: Option<usize>,
     pub settings: AccountSettings,
     pub last_synced: u64,
 }
@@ -16,6 +23,9 @@ impl AccountData {
     pub fn from_account(account: &Account) -> Self {
         Self {
             pubkey: account.pubkey.to_hex(),
+            name: None,
+            current: false,
+            unread: None,
             settings: account.settings.clone(),
             last_synced: account.last_synced.as_u64(),
         }
@@ -28,8 +38,7 @@ pub struct AccountManager {
 }
 
 impl AccountManager {
-    pub async fn new() -> Result<Self> {
-        let storage = Storage::new().await?;
+    pub async fn new(storage: Storage) -> Result<Self> {
         let mut manager = Self {
             current_account: None,
             storage,
@@ -70,7 +79,7 @@ impl AccountManager {
             }
         }
         
-        Err(anyhow::anyhow!("Account not found for pubkey: {}", pubkey))
+        Err(CliError::NotFound(format!("Account not found for pubkey: {}", pubkey)).into())
     }
 
     pub async fn fetch_accounts(&self) -> Result<Vec<AccountData>> {
@@ -80,7 +89,75 @@ impl AccountManager {
         let accounts = whitenoise.fetch_accounts().await
             .map_err(|e| anyhow::anyhow!("Failed to fetch accounts: {:?}", e))?;
         
-        Ok(accounts.values().map(AccountData::from_account).collect())
+        let names = self.storage.load_account_names().await?;
+        let current = self.current_account.as_ref().map(|a| a.pubkey);
+        let mut accounts: Vec<AccountData> = accounts.values()
+            .map(|account| {
+                let mut data = AccountData::from_account(account);
+                data.name = names.get(&data.pubkey).cloned();
+                data.current = current == Some(account.pubkey);
+                data
+            })
+            .collect();
+        accounts.sort_by(|a, b| (&a.name, &a.pubkey).cmp(&(&b.name, &b.pubkey)));
+        Ok(accounts)
+    }
+
+    /// Every account logged in on this machine
+    pub async fn logged_in_accounts(&self) -> Result<Vec<Account>> {
+        let whitenoise = Whitenoise::get_instance()
+            .map_err(|e| anyhow::anyhow!("Failed to get WhiteNoise instance: {:?}", e))?;
+
+        let accounts = whitenoise.fetch_accounts().await
+            .map_err(|e| anyhow::anyhow!("Failed to fetch accounts: {:?}", e))?;
+
+        let mut accounts: Vec<Account> = accounts.into_values().collect();
+        accounts.sort_by_key(|a| a.pubkey);
+        Ok(accounts)
+    }
+
+    /// Find a local account by hex key, npub, nprofile, NIP-05 identifier
+    /// or cached profile name
+    pub async fn resolve_account(&self, query: &str) -> Result<Account> {
+        let whitenoise = Whitenoise::get_instance()
+            .map_err(|e| anyhow::anyhow!("Failed to get WhiteNoise instance: {:?}", e))?;
+
+        let accounts = whitenoise.fetch_accounts().await
+            .map_err(|e| anyhow::anyhow!("Failed to fetch accounts: {:?}", e))?;
+
+        let identity = match identifiers::parse_key(query)? {
+            Some(identity) => Some(identity),
+            None => identifiers::resolve_nip05(query).await?,
+        };
+        if let Some(identity) = identity {
+            return accounts.get(&identity.pubkey).cloned()
+                .ok_or_else(|| CliError::NotFound(format!("No local account for {}", query)).into());
+        }
+
+        let names = self.storage.load_account_names().await?;
+        let mut matches = accounts.values()
+            .filter(|a| names.get(&a.pubkey.to_hex()).is_some_and(|name| name.eq_ignore_ascii_case(query.trim())));
+        match (matches.next(), matches.next()) {
+            (Some(account), None) => Ok(account.clone()),
+            (Some(_), Some(_)) => Err(CliError::InvalidInput(format!(
+                "Several accounts are named {}; use the npub instead", query
+            )).into()),
+            (None, _) => Err(CliError::NotFound(format!("No local account named {}", query)).into()),
+        }
+    }
+
+    /// Make another logged-in account the current one and remember it for
+    /// the next run
+    pub async fn switch_account(&mut self, mut account: Account) -> Result<Account> {
+        let whitenoise = Whitenoise::get_instance()
+            .map_err(|e| anyhow::anyhow!("Failed to get WhiteNoise instance: {:?}", e))?;
+
+        // Same relay repair as auto-login, but quiet so JSON output stays clean
+        let _ = whitenoise.fix_account_empty_relays(&mut account).await;
+
+        self.storage.save_current_account_pubkey(&account.pubkey.to_hex()).await?;
+        self.current_account = Some(account.clone());
+        Ok(account)
     }
 
     pub async fn create_identity(&mut self) -> Result<Account> {
@@ -159,7 +236,7 @@ impl AccountManager {
             whitenoise.export_account_nsec(account).await
                 .map_err(|e| anyhow::anyhow!("Failed to export nsec: {:?}", e))
         } else {
-            Err(anyhow::anyhow!("No account logged in"))
+            Err(CliError::NotLoggedIn.into())
         }
     }
 
@@ -171,7 +248,7 @@ impl AccountManager {
             whitenoise.export_account_npub(account).await
                 .map_err(|e| anyhow::anyhow!("Failed to export npub: {:?}", e))
         } else {
-            Err(anyhow::anyhow!("No account logged in"))
+            Err(CliError::NotLoggedIn.into())
         }
     }
 
@@ -181,10 +258,15 @@ impl AccountManager {
                 .map_err(|e| anyhow::anyhow!("Failed to get WhiteNoise instance: {:?}", e))?;
             
             // Use the account's relays to fetch metadata
-            whitenoise.fetch_metadata_from(account.nip65_relays.clone(), account.pubkey).await
-                .map_err(|e| anyhow::anyhow!("Failed to fetch metadata: {:?}", e))
+            let metadata = whitenoise.fetch_metadata_from(account.nip65_relays.clone(), account.pubkey).await
+                .map_err(|e| anyhow::anyhow!("Failed to fetch metadata: {:?}", e))?;
+
+            if let Some(name) = metadata.as_ref().and_then(|m| m.name.as_ref()) {
+                self.storage.save_account_name(&account.pubkey.to_hex(), name).await?;
+            }
+            Ok(metadata)
         } else {
-            Err(anyhow::anyhow!("No account logged in"))
+            Err(CliError::NotLoggedIn.into())
         }
     }
 
@@ -194,9 +276,14 @@ impl AccountManager {
                 .map_err(|e| anyhow::anyhow!("Failed to get WhiteNoise instance: {:?}", e))?;
             
             whitenoise.update_metadata(metadata, account).await
-                .map_err(|e| anyhow::anyhow!("Failed to update metadata: {:?}", e))
+                .map_err(|e| anyhow::anyhow!("Failed to update metadata: {:?}", e))?;
+
+            if let Some(name) = &metadata.name {
+                self.storage.save_account_name(&account.pubkey.to_hex(), name).await?;
+            }
+            Ok(())
         } else {
-            Err(anyhow::anyhow!("No account logged in"))
+            Err(CliError::NotLoggedIn.into())
         }
     }
 
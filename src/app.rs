@@ -1,16 +1,20 @@
 use anyhow::Result;
 use console::{style, Term};
 use dialoguer::{theme::ColorfulTheme, Select, Input, Confirm};
-use whitenoise::{Account, PublicKey, RelayType, Metadata, Whitenoise};
+use whitenoise::{Account, PublicKey, Metadata, Whitenoise};
 
 use crate::{
     account::AccountManager, 
+    chat::ChatView,
     contacts::ContactManager, 
-    groups::{GroupManager, GroupData}, 
-    relays::RelayManager,
+    groups::{GroupManager, GroupData, GroupFilter, OutgoingMessage}, 
+    identifiers,
+    errors::CliError,
+    relays::{NetworkPolicy, RelayManager, DEFAULT_RELAY_PROFILE},
     ui, 
     storage::Storage,
-    whitenoise_config::WhitenoiseManager
+    whitenoise_config::WhitenoiseManager,
+    config::Config
 };
 
 pub struct App {
@@ -21,15 +25,20 @@ pub struct App {
     pub storage: Storage,
     pub term: Term,
     pub whitenoise_manager: WhitenoiseManager,
+    pub config: Config,
+    /// Group state filter used by the interactive group menu
+    pub group_filter: Option<GroupFilter>,
+    /// Relay profile given with `--relay-profile`; wins over the account's choice
+    pub relay_profile: Option<String>,
 }
 
 impl App {
-    pub async fn new(whitenoise_manager: WhitenoiseManager) -> Result<Self> {
-        let storage = Storage::new().await?;
-        let account_manager = AccountManager::new().await?;
+    pub async fn new(whitenoise_manager: WhitenoiseManager, config: Config) -> Result<Self> {
+        let storage = Storage::open(config.state_dir()?)?;
+        let account_manager = AccountManager::new(Storage::open(config.state_dir()?)?).await?;
         let contacts = storage.load_contacts().await.unwrap_or_else(|_| ContactManager::new());
         let groups = GroupManager::new();
-        let relays = RelayManager::new();
+        let relays = RelayManager::new(config.relay_config());
         
         Ok(Self {
             account_manager,
@@ -39,11 +48,60 @@ impl App {
             storage,
             term: Term::stdout(),
             whitenoise_manager,
+            config,
+            group_filter: None,
+            relay_profile: None,
         })
     }
 
+    /// Activate the relay profile from `--relay-profile`, else the current
+    /// account's choice, else the config file, else `default`
+    pub async fn apply_relay_profile(&mut self) -> Result<()> {
+        let account_profile = match self.account_manager.get_current_account() {
+            Some(account) => self.storage.load_account_relay_profile(&account.pubkey.to_hex()).await?,
+            None => None,
+        };
+        if let Some(offline) = NetworkPolicy::current().relay_config() {
+            self.relays.set_config(offline);
+            return Ok(());
+        }
+
+        let name = self.relay_profile.clone()
+            .or(account_profile)
+            .or_else(|| self.config.relay_profile.clone())
+            .unwrap_or_else(|| DEFAULT_RELAY_PROFILE.to_string());
+        self.relays.set_config(self.config.relay_profile(&name)?);
+        Ok(())
+    }
+
+    /// In offline mode, refuse to go on while the current account would
+    /// still publish to relays outside the allowed list
+    pub fn check_account_relays(&self) -> Result<()> {
+        match self.account_manager.get_current_account() {
+            Some(account) => Self::check_relays_of(account, "replace them with `relay profile use local-dev --publish`"),
+            None => Ok(()),
+        }
+    }
+
+    fn check_relays_of(account: &Account, hint: &str) -> Result<()> {
+        let relays = account.nip65_relays.iter()
+            .chain(&account.inbox_relays)
+            .chain(&account.key_package_relays)
+            .map(|url| url.to_string());
+        NetworkPolicy::current().check_relays(relays)
+            .map_err(|e| CliError::Relay(format!("{} (account relays; {})", e, hint)).into())
+    }
+
     pub async fn run_main_menu(&mut self) -> Result<bool> {
         self.term.clear_screen()?;
+
+        // The account may have changed since the last pass through the menu
+        if let Err(e) = self.apply_relay_profile().await {
+            println!("{} {}", style("⚠️ Using the default relay profile:").yellow(), e);
+        }
+        if let Err(e) = self.check_account_relays() {
+            println!("{} {}", style("⚠️").yellow(), e);
+        }
         
         if !self.account_manager.is_logged_in() {
             return self.account_setup_menu().await;
@@ -56,12 +114,24 @@ impl App {
                     println!("{} {}", style("Name:").dim(), style(name).dim());
                 }
             }
+            println!("{} {}", style("Relay profile:").dim(), style(&self.relays.get_config().profile).dim());
             println!();
         }
 
+        let pending_invites = match self.account_manager.get_current_account() {
+            Some(account) => self.groups.fetch_invites(account, false).await.map(|i| i.len()).unwrap_or(0),
+            None => 0,
+        };
+        let invitations = if pending_invites > 0 {
+            format!("📨 Invitations ({})", pending_invites)
+        } else {
+            "📨 Invitations".to_string()
+        };
+
         let options = vec![
             "💬 Group Conversations",
             "📩 Direct Messages",
+            invitations.as_str(),
             "👥 Manage Contacts", 
             "📡 Relay Settings",
             "🔑 Account Settings",
@@ -77,10 +147,11 @@ impl App {
         match selection {
             0 => self.group_conversations_menu().await,
             1 => self.direct_messages_menu().await,
-            2 => self.manage_contacts_menu().await,
-            3 => self.relay_settings_menu().await,
-            4 => self.account_settings_menu().await,
-            5 => Ok(false),
+            2 => self.invitations_menu().await,
+            3 => self.manage_contacts_menu().await,
+            4 => self.relay_settings_menu().await,
+            5 => self.account_settings_menu().await,
+            6 => Ok(false),
             _ => Ok(true),
         }
     }
@@ -93,6 +164,7 @@ impl App {
         let options = vec![
             "🔑 Create New Identity",
             "🔓 Login with Existing Key",
+            "🔄 Switch to Logged-in Account",
             "📋 View All Accounts",
             "❌ Exit",
         ];
@@ -105,8 +177,12 @@ impl App {
         match selection {
             0 => self.create_new_identity().await,
             1 => self.login_existing_account().await,
-            2 => self.view_all_accounts().await,
-            3 => Ok(false),
+            2 => {
+                self.switch_account().await?;
+                Ok(true)
+            }
+            3 => self.view_all_accounts().await,
+            4 => Ok(false),
             _ => Ok(true),
         }
     }
@@ -115,48 +191,36 @@ impl App {
         println!("{}", style("🆕 Creating New Identity").bold().yellow());
         println!();
         
-        let _account = self.account_manager.create_identity().await?;
+        let mut current_account = self.account_manager.create_identity().await?;
         
         // Set up default relay configuration
-        let current_account = self.account_manager.get_current_account().unwrap();
         for relay_type in RelayManager::all_relay_types() {
-            let default_relays = match relay_type {
-                RelayType::Nostr => vec![
-                    "ws://localhost:10547".to_string(),
-                    "wss://relay.damus.io".to_string(),
-                    "wss://relay.primal.net".to_string(),
-                    "wss://nos.lol".to_string(),
-                ],
-                RelayType::Inbox => vec![
-                    "ws://localhost:10547".to_string(),
-                    "wss://relay.damus.io".to_string(),
-                    "wss://relay.primal.net".to_string(),
-                ],
-                RelayType::KeyPackage => vec![
-                    "ws://localhost:10547".to_string(),
-                    "wss://relay.damus.io".to_string(),
-                    "wss://nos.lol".to_string(),
-                ],
-            };
+            let default_relays = self.relays.get_relays_for_type(&relay_type).clone();
             
-            if let Err(e) = self.relays.update_relays(current_account, relay_type, default_relays).await {
-                println!("{} Warning: Failed to set up {} relays: {}", 
+            match self.relays.update_relays(&mut current_account, relay_type, default_relays).await {
+                Ok(()) => println!("{} {} relays updated successfully!",
+                    style("✅").green(),
+                    self.relays.relay_type_name(&relay_type)
+                ),
+                Err(e) => println!("{} Warning: Failed to set up {} relays: {}", 
                     style("⚠️").yellow(), 
                     self.relays.relay_type_name(&relay_type),
                     e
-                );
+                ),
             }
         }
 
         // Clean up unwanted relays (like purplepag.es that cause connection errors)
-        if let Err(e) = self.relays.cleanup_unwanted_relays(current_account).await {
+        if let Err(e) = self.relays.cleanup_unwanted_relays(&current_account).await {
             println!("{} Warning: Failed to clean up unwanted relays: {}", style("⚠️").yellow(), e);
         }
 
         // Publish key package
-        if let Err(e) = self.relays.publish_key_package(current_account).await {
-            println!("{} Warning: Failed to publish key package: {}", style("⚠️").yellow(), e);
+        match self.relays.publish_key_package(&current_account).await {
+            Ok(()) => println!("{}", style("ℹ️ Key packages are published automatically during account setup.").yellow()),
+            Err(e) => println!("{} Warning: Failed to publish key package: {}", style("⚠️").yellow(), e),
         }
+        self.account_manager.set_current_account(current_account);
 
         // Set up basic metadata
         self.setup_profile_metadata().await?;
@@ -240,9 +304,10 @@ impl App {
                     println!("{}", style("No accounts found.").dim());
                 } else {
                     for (i, account) in accounts.iter().enumerate() {
-                        println!("{}. {}", 
+                        println!("{}. {} {}", 
                             style(format!("{}", i + 1)).bold(),
-                            style(&account.pubkey[..16]).green()
+                            style(&account.pubkey[..16]).green(),
+                            style(account.name.as_deref().unwrap_or("")).dim()
                         );
                     }
                 }
@@ -256,15 +321,69 @@ impl App {
         Ok(true)
     }
 
+    /// Pick one of the logged-in accounts and make it current. Returns
+    /// whether the current account changed.
+    async fn switch_account(&mut self) -> Result<bool> {
+        println!("{}", style("🔄 Switch Account").bold().cyan());
+        println!();
+
+        let accounts = self.account_manager.logged_in_accounts().await?;
+        if accounts.is_empty() {
+            println!("{}", style("No logged-in accounts on this machine.").yellow());
+            ui::wait_for_enter("Press Enter to continue...");
+            return Ok(false);
+        }
+
+        println!("{}", style("Counting unread messages...").dim());
+        let names = self.account_manager.fetch_accounts().await?;
+        let mut options = Vec::new();
+        for account in &accounts {
+            let pubkey = account.pubkey.to_hex();
+            let data = names.iter().find(|a| a.pubkey == pubkey);
+            let name = data.and_then(|a| a.name.clone()).unwrap_or_else(|| "(no name)".to_string());
+            let current = if data.is_some_and(|a| a.current) { " (current)" } else { "" };
+            let markers = self.storage.load_read_markers(&pubkey).await?;
+            let unread = match self.groups.unread_count(account, &markers).await {
+                Ok(0) => String::new(),
+                Ok(count) => format!(" · {} unread", count),
+                Err(_) => " · ? unread".to_string(),
+            };
+            options.push(format!("{} {}…{}{}", name, &pubkey[..16], current, unread));
+        }
+        options.push("🔙 Back".to_string());
+
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Switch to:")
+            .items(&options)
+            .default(0)
+            .interact()?;
+        let Some(account) = accounts.get(selection).cloned() else {
+            return Ok(false);
+        };
+        if self.account_manager.get_current_account().is_some_and(|a| a.pubkey == account.pubkey) {
+            return Ok(false);
+        }
+
+        let account = self.account_manager.switch_account(account).await?;
+        self.groups = GroupManager::new();
+        self.group_filter = None;
+        println!("{} Switched to {}", style("✅").green(), style(&account.pubkey.to_hex()[..16]).green());
+        ui::wait_for_enter("Press Enter to continue...");
+        Ok(true)
+    }
+
     async fn group_conversations_menu(&mut self) -> Result<bool> {
         loop {
             self.term.clear_screen()?;
-            println!("{}", style("💬 Group Conversations").bold().cyan());
+            println!("{} {}",
+                style("💬 Group Conversations").bold().cyan(),
+                style(format!("(showing {})", GroupFilter::label(self.group_filter))).dim()
+            );
             println!();
 
             // Fetch groups for current account
-            if let Some(account) = self.account_manager.get_current_account() {
-                match self.groups.fetch_groups(account).await {
+            if let Some(account) = self.account_manager.get_current_account().cloned() {
+                match self.visible_groups(&account).await {
                     Ok(groups) => {
                         if groups.is_empty() {
                             println!("{}", style("No groups yet. Create one to get started!").dim().italic());
@@ -294,8 +413,12 @@ impl App {
             println!();
             let options = vec![
                 "💬 Join Group Chat",
+                "🗨️  Reply, React or Delete",
                 "➕ Create New Group",
                 "👥 Manage Group Members",
+                "🗄️  Archive or Unarchive Group",
+                "🚪 Leave Group",
+                "🔍 Filter Groups",
                 "🔙 Back to Main Menu",
             ];
 
@@ -306,14 +429,113 @@ impl App {
 
             match selection {
                 0 => self.join_group_chat().await?,
-                1 => self.create_new_group().await?,
-                2 => self.manage_group_members().await?,
-                3 => return Ok(true),
+                1 => self.message_actions().await?,
+                2 => self.create_new_group().await?,
+                3 => self.manage_group_members().await?,
+                4 => self.toggle_group_archived().await?,
+                5 => self.leave_group().await?,
+                6 => self.select_group_filter()?,
+                7 => return Ok(true),
                 _ => {}
             }
         }
     }
 
+    /// Groups for the interactive menu, honouring the current filter
+    async fn visible_groups(&mut self, account: &Account) -> Result<Vec<GroupData>> {
+        let archived = self.storage.load_archived_groups(&account.pubkey.to_hex()).await?;
+        self.groups.list_groups(account, &archived, self.group_filter).await
+    }
+
+    fn select_group_filter(&mut self) -> Result<()> {
+        let filters = [None, Some(GroupFilter::Active), Some(GroupFilter::Inactive), Some(GroupFilter::Archived)];
+        let labels: Vec<&str> = filters.iter().map(|f| GroupFilter::label(*f)).collect();
+        let current = filters.iter().position(|f| *f == self.group_filter).unwrap_or(0);
+
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Show groups:")
+            .items(&labels)
+            .default(current)
+            .interact()?;
+        self.group_filter = filters[selection];
+        Ok(())
+    }
+
+    async fn toggle_group_archived(&mut self) -> Result<()> {
+        let account = if let Some(account) = self.account_manager.get_current_account() {
+            account.clone()
+        } else {
+            return Ok(());
+        };
+
+        let pubkey = account.pubkey.to_hex();
+        let mut archived = self.storage.load_archived_groups(&pubkey).await?;
+        let mut groups = self.groups.fetch_groups(&account).await?;
+        for group in &mut groups {
+            group.archived = archived.contains(&group.mls_group_id);
+        }
+        if groups.is_empty() {
+            println!("{}", style("No groups yet.").yellow());
+            ui::wait_for_enter("Press Enter to continue...");
+            return Ok(());
+        }
+
+        let group_options: Vec<String> = groups.iter()
+            .map(|g| if g.archived { format!("{} (archived)", g.name) } else { g.name.clone() })
+            .collect();
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Select group to archive or unarchive:")
+            .items(&group_options)
+            .interact()?;
+
+        let group = &groups[selection];
+        if group.archived {
+            archived.remove(&group.mls_group_id);
+            println!("{} {} is visible again", style("✅").green(), style(&group.name).green());
+        } else {
+            archived.insert(group.mls_group_id.clone());
+            println!("{} {} archived", style("✅").green(), style(&group.name).green());
+        }
+        self.storage.save_archived_groups(&pubkey, &archived).await?;
+        ui::wait_for_enter("Press Enter to continue...");
+        Ok(())
+    }
+
+    async fn leave_group(&mut self) -> Result<()> {
+        let account = if let Some(account) = self.account_manager.get_current_account() {
+            account.clone()
+        } else {
+            return Ok(());
+        };
+
+        let groups = self.visible_groups(&account).await?;
+        if groups.is_empty() {
+            println!("{}", style("No groups to leave.").yellow());
+            ui::wait_for_enter("Press Enter to continue...");
+            return Ok(());
+        }
+
+        let group_options: Vec<&str> = groups.iter().map(|g| g.name.as_str()).collect();
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Select group to leave:")
+            .items(&group_options)
+            .interact()?;
+        let group = &groups[selection];
+
+        let confirmed = Confirm::with_theme(&ColorfulTheme::default())
+            .with_prompt(format!("Leave {}? You will need a new invitation to rejoin", group.name))
+            .default(false)
+            .interact()?;
+        if confirmed {
+            let group_id = GroupManager::group_id_from_string(&group.mls_group_id)?;
+            if let Err(e) = self.groups.leave_group(&account, &group_id).await {
+                println!("{} {}", style("❌").red(), e);
+            }
+            ui::wait_for_enter("Press Enter to continue...");
+        }
+        Ok(())
+    }
+
     async fn join_group_chat(&mut self) -> Result<()> {
         let account_clone = if let Some(account) = self.account_manager.get_current_account() {
             account.clone()
@@ -321,7 +543,7 @@ impl App {
             return Ok(());
         };
 
-        let groups = self.groups.fetch_groups(&account_clone).await?;
+        let groups = self.visible_groups(&account_clone).await?;
         
         if groups.is_empty() {
             println!("{}", style("No groups available to join.").yellow());
@@ -346,64 +568,105 @@ impl App {
 
     async fn start_group_chat(&mut self, account: &Account, group: &GroupData) -> Result<()> {
         let group_id = GroupManager::group_id_from_string(&group.mls_group_id)?;
-        
-        println!("{} Joining group '{}'...", style("🔄").yellow(), style(&group.name).bold());
 
-        loop {
-            self.term.clear_screen()?;
-            
-            println!("{}", style(format!("💬 Group Chat: {}", group.name)).bold().cyan());
-            println!("{}", style("─".repeat(50)).dim());
-            
-            // Fetch and display recent messages
-            match self.groups.fetch_aggregated_messages_for_group(account, &group_id).await {
-                Ok(messages) => {
-                    if messages.is_empty() {
-                        println!("{}", style("No messages yet. Start the conversation!").dim().italic());
-                    } else {
-                        let recent_messages = messages.iter().rev().take(10).rev();
-                        for msg in recent_messages {
-                            let timestamp = chrono::DateTime::from_timestamp(msg.created_at.as_u64() as i64, 0)
-                                .unwrap_or_default()
-                                .format("%H:%M");
-                            let author_short = &msg.author.to_hex()[..8];
-                            println!("{} {} {}", 
-                                style(format!("[{}]", timestamp)).dim(),
-                                style(format!("{}:", author_short)).bold().blue(), 
-                                msg.content
-                            );
-                        }
-                    }
-                }
-                Err(e) => {
-                    println!("{} Failed to fetch messages: {}", style("❌").red(), e);
-                }
-            }
-            
-            println!("{}", style("─".repeat(50)).dim());
-            println!();
-            
-            let input: String = Input::new()
-                .with_prompt(&format!("💭 Message to {} (or 'quit' to exit)", group.name))
-                .allow_empty(true)
-                .interact()?;
+        let labels = self.contacts.list().iter()
+            .map(|c| (c.public_key.clone(), c.name.clone()))
+            .collect();
 
-            if input.trim().to_lowercase() == "quit" || input.trim().is_empty() {
-                break;
-            }
+        ChatView::new(&group.name, account.pubkey.to_hex(), labels)
+            .run(account, &group_id)
+            .await?;
 
-            match self.groups.send_message_to_group(account, &group_id, input.trim().to_string(), 9).await {
-                Ok(_) => {
-                    println!("{} Message sent!", style("✅").green());
-                    tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
-                }
-                Err(e) => {
-                    println!("{} Failed to send message: {}", style("❌").red(), e);
-                    ui::wait_for_enter("Press Enter to continue...");
+        // Everything up to now was on screen while the chat was open
+        let read_at = chrono::Utc::now().timestamp() as u64;
+        self.storage.mark_group_read(&account.pubkey.to_hex(), &group.mls_group_id, read_at).await
+    }
+
+    async fn message_actions(&mut self) -> Result<()> {
+        let account = if let Some(account) = self.account_manager.get_current_account() {
+            account.clone()
+        } else {
+            return Ok(());
+        };
+
+        let groups = self.visible_groups(&account).await?;
+        if groups.is_empty() {
+            println!("{}", style("No groups yet.").yellow());
+            ui::wait_for_enter("Press Enter to continue...");
+            return Ok(());
+        }
+
+        let group_options: Vec<&str> = groups.iter().map(|g| g.name.as_str()).collect();
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Select group:")
+            .items(&group_options)
+            .interact()?;
+        let group_id = GroupManager::group_id_from_string(&groups[selection].mls_group_id)?;
+
+        let messages = self.groups.fetch_aggregated_messages_for_group(&account, &group_id).await?;
+        let recent: Vec<_> = messages.iter().filter(|m| !m.is_deleted).rev().take(20).collect();
+        if recent.is_empty() {
+            println!("{}", style("No messages in this group yet.").yellow());
+            ui::wait_for_enter("Press Enter to continue...");
+            return Ok(());
+        }
+
+        let message_options: Vec<String> = recent
+            .iter()
+            .map(|m| {
+                let timestamp = chrono::DateTime::from_timestamp(m.created_at.as_u64() as i64, 0)
+                    .unwrap_or_default()
+                    .format("%m/%d %H:%M");
+                let content: String = m.content.chars().take(50).collect();
+                format!("[{}] {}: {}", timestamp, self.contact_label(&m.author.to_hex()), content)
+            })
+            .collect();
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Select message (newest first):")
+            .items(&message_options)
+            .interact()?;
+        let target = recent[selection];
+
+        let mut actions = vec!["↩️  Reply", "😀 React"];
+        if target.author == account.pubkey {
+            actions.push("🗑️  Delete");
+        }
+        actions.push("🔙 Back");
+
+        let action = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Action:")
+            .items(&actions)
+            .interact()?;
+
+        let outcome = match actions[action] {
+            "↩️  Reply" => {
+                let content: String = Input::new().with_prompt("Reply").interact()?;
+                self.groups.send_message_to_group(&account, &group_id, OutgoingMessage::reply(target, content)).await
+                    .map(|_| "Reply sent!")
+            }
+            "😀 React" => {
+                let emoji: String = Input::new()
+                    .with_prompt("Reaction")
+                    .default("👍".to_string())
+                    .interact()?;
+                self.groups.send_message_to_group(&account, &group_id, OutgoingMessage::reaction(target, emoji)).await
+                    .map(|_| "Reaction sent!")
+            }
+            "🗑️  Delete" => {
+                if !Confirm::new().with_prompt("Delete this message for everyone?").interact()? {
+                    return Ok(());
                 }
+                self.groups.send_message_to_group(&account, &group_id, OutgoingMessage::deletion(target, None)).await
+                    .map(|_| "Message deleted!")
             }
-        }
+            _ => return Ok(()),
+        };
 
+        match outcome {
+            Ok(message) => println!("{} {}", style("✅").green(), message),
+            Err(e) => println!("{} {}", style("❌").red(), e),
+        }
+        ui::wait_for_enter("Press Enter to continue...");
         Ok(())
     }
 
@@ -446,9 +709,159 @@ impl App {
     }
 
     async fn manage_group_members(&mut self) -> Result<()> {
-        println!("{}", style("👥 Group member management not yet implemented").yellow());
-        ui::wait_for_enter("Press Enter to continue...");
-        Ok(())
+        let account = if let Some(account) = self.account_manager.get_current_account() {
+            account.clone()
+        } else {
+            return Ok(());
+        };
+
+        let groups = self.visible_groups(&account).await?;
+        if groups.is_empty() {
+            println!("{}", style("No groups to manage.").yellow());
+            ui::wait_for_enter("Press Enter to continue...");
+            return Ok(());
+        }
+
+        let group_options: Vec<&str> = groups.iter().map(|g| g.name.as_str()).collect();
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Select group to manage:")
+            .items(&group_options)
+            .interact()?;
+
+        let group = &groups[selection];
+        let group_id = GroupManager::group_id_from_string(&group.mls_group_id)?;
+
+        loop {
+            self.term.clear_screen()?;
+            println!("{}", style(format!("👥 Members of {}", group.name)).bold().cyan());
+            println!();
+
+            let membership = self.groups.membership(&account, &group_id).await?;
+            for member in &membership.members {
+                let badge = if membership.admins.contains(member) { " 🛡️  admin" } else { "" };
+                println!("  • {} {}{}",
+                    style(self.contact_label(member)).green(),
+                    style(format!("({})", &member[..16])).dim(),
+                    style(badge).yellow()
+                );
+            }
+            if let Some(epoch) = membership.epoch {
+                println!();
+                println!("{} {}", style("Epoch:").dim(), style(epoch).dim());
+            }
+            println!();
+
+            let options = vec![
+                "➕ Add Member",
+                "➖ Remove Member",
+                "⬆️  Promote to Admin",
+                "⬇️  Demote Admin",
+                "🔙 Back",
+            ];
+
+            let choice = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("Member Options:")
+                .items(&options)
+                .interact()?;
+
+            let outcome = match choice {
+                0 => self.add_group_member(&account, &group_id, &membership.members).await,
+                1 => {
+                    let others: Vec<String> = membership.members.iter()
+                        .filter(|m| **m != account.pubkey.to_hex())
+                        .cloned()
+                        .collect();
+                    match self.select_member("Select member to remove:", &others)? {
+                        Some(member) => {
+                            let pubkey = PublicKey::from_hex(&member)?;
+                            self.groups.ensure_admin(&account, &group_id).await?;
+                            self.groups.remove_members_from_group(&account, &group_id, vec![pubkey]).await
+                        }
+                        None => Ok(()),
+                    }
+                }
+                2 => {
+                    let candidates: Vec<String> = membership.members.iter()
+                        .filter(|m| !membership.admins.contains(m))
+                        .cloned()
+                        .collect();
+                    match self.select_member("Select member to promote:", &candidates)? {
+                        Some(member) => self.groups
+                            .promote_admin(&account, &group_id, PublicKey::from_hex(&member)?)
+                            .await
+                            .map(|_| ()),
+                        None => Ok(()),
+                    }
+                }
+                3 => match self.select_member("Select admin to demote:", &membership.admins)? {
+                    Some(member) => self.groups
+                        .demote_admin(&account, &group_id, PublicKey::from_hex(&member)?)
+                        .await
+                        .map(|_| ()),
+                    None => Ok(()),
+                },
+                _ => return Ok(()),
+            };
+
+            if let Err(e) = outcome {
+                println!("{} {}", style("❌").red(), e);
+                ui::wait_for_enter("Press Enter to continue...");
+            }
+        }
+    }
+
+    async fn add_group_member(&mut self, account: &Account, group_id: &whitenoise::GroupId, members: &[String]) -> Result<()> {
+        let candidates: Vec<String> = self.contacts.list().iter()
+            .map(|c| c.public_key.clone())
+            .filter(|pk| !members.contains(pk))
+            .collect();
+
+        let mut options: Vec<String> = candidates.iter()
+            .map(|pk| format!("{} ({})", self.contact_label(pk), &pk[..16]))
+            .collect();
+        options.push("⌨️  Enter public key manually".to_string());
+
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Select contact to add:")
+            .items(&options)
+            .interact()?;
+
+        let pubkey = if selection < candidates.len() {
+            PublicKey::from_hex(&candidates[selection])?
+        } else {
+            let input: String = Input::new()
+                .with_prompt("Member (npub, nprofile, hex, name@domain or contact name)")
+                .interact()?;
+            identifiers::resolve(&input, &self.contacts).await?.pubkey
+        };
+
+        self.groups.ensure_admin(account, group_id).await?;
+        self.groups.add_members_to_group(account, group_id, vec![pubkey]).await
+    }
+
+    fn select_member(&self, prompt: &str, members: &[String]) -> Result<Option<String>> {
+        if members.is_empty() {
+            println!("{}", style("No eligible members.").dim());
+            ui::wait_for_enter("Press Enter to continue...");
+            return Ok(None);
+        }
+
+        let options: Vec<String> = members.iter()
+            .map(|pk| format!("{} ({})", self.contact_label(pk), &pk[..16]))
+            .collect();
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt(prompt)
+            .items(&options)
+            .interact()?;
+
+        Ok(Some(members[selection].clone()))
+    }
+
+    /// Contact name for a hex public key, falling back to a shortened key
+    fn contact_label(&self, pubkey: &str) -> String {
+        self.contacts.get(pubkey)
+            .map(|c| c.name.clone())
+            .unwrap_or_else(|| pubkey[..8].to_string())
     }
 
     async fn direct_messages_menu(&mut self) -> Result<bool> {
@@ -517,6 +930,77 @@ impl App {
         Ok(())
     }
 
+    async fn invitations_menu(&mut self) -> Result<bool> {
+        let account = if let Some(account) = self.account_manager.get_current_account() {
+            account.clone()
+        } else {
+            return Ok(true);
+        };
+
+        loop {
+            self.term.clear_screen()?;
+            println!("{}", style("📨 Invitations").bold().cyan());
+            println!();
+
+            let invites = self.groups.fetch_invites(&account, false).await?;
+            if invites.is_empty() {
+                println!("{}", style("No pending invitations.").dim().italic());
+                ui::wait_for_enter("Press Enter to continue...");
+                return Ok(true);
+            }
+
+            let mut options: Vec<String> = invites
+                .iter()
+                .map(|i| format!("{} (from {}, {} members)", i.group_name, self.contact_label(&i.invited_by), i.member_count))
+                .collect();
+            options.push("🔙 Back to Main Menu".to_string());
+
+            let selection = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("Select an invitation:")
+                .items(&options)
+                .interact()?;
+
+            if selection == invites.len() {
+                return Ok(true);
+            }
+
+            let invite = &invites[selection];
+            println!();
+            println!("{} {}", style("Group:").bold(), style(&invite.group_name).green());
+            if !invite.group_description.is_empty() {
+                println!("{} {}", style("Description:").bold(), invite.group_description);
+            }
+            println!("{} {} {}",
+                style("Invited by:").bold(),
+                self.contact_label(&invite.invited_by),
+                style(format!("({})", &invite.invited_by[..16])).dim()
+            );
+            println!("{} {}", style("Members:").bold(), invite.member_count);
+            println!("{} {}", style("Relays:").bold(), invite.relays.join(", "));
+            println!();
+
+            let actions = vec!["✅ Accept and Join", "🚫 Decline", "🔙 Back"];
+            let action = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("What would you like to do?")
+                .items(&actions)
+                .interact()?;
+
+            let outcome = match action {
+                0 => self.groups.accept_invite(&account, invite).await
+                    .map(|_| format!("Joined '{}'", invite.group_name)),
+                1 => self.groups.decline_invite(&account, invite).await
+                    .map(|_| format!("Declined invitation to '{}'", invite.group_name)),
+                _ => continue,
+            };
+
+            match outcome {
+                Ok(message) => println!("{} {}", style("✅").green(), message),
+                Err(e) => println!("{} {}", style("❌").red(), e),
+            }
+            ui::wait_for_enter("Press Enter to continue...");
+        }
+    }
+
     async fn fetch_contacts(&mut self) -> Result<()> {
         if let Some(account) = self.account_manager.get_current_account() {
             println!("{}", style("📡 Fetching contacts from relays...").yellow());
@@ -578,10 +1062,14 @@ impl App {
             .interact()?;
 
         let public_key: String = Input::new()
-            .with_prompt("Contact's public key (npub... or hex)")
+            .with_prompt("Contact's public key (npub, nprofile, hex or name@domain)")
             .interact()?;
 
-        match self.contacts.add(name.clone(), public_key).await {
+        let added = match identifiers::resolve(&public_key, &self.contacts).await {
+            Ok(identity) => self.contacts.add(name.clone(), identity, self.relays.lookup_relays()).await,
+            Err(e) => Err(e),
+        };
+        match added {
             Ok(_) => {
                 println!("{} Contact '{}' added successfully!", style("✅").green(), name);
             }
@@ -674,6 +1162,7 @@ impl App {
                 "📋 View Current Relays",
                 "➕ Add Relay",
                 "🗑️  Remove Relay",
+                "🗂️  Relay Profile",
                 "🔙 Back to Main Menu",
             ];
 
@@ -686,12 +1175,72 @@ impl App {
                 0 => self.view_current_relays().await?,
                 1 => self.add_relay().await?,
                 2 => self.remove_relay().await?,
-                3 => return Ok(true),
+                3 => self.select_relay_profile().await?,
+                4 => return Ok(true),
                 _ => {}
             }
         }
     }
 
+    /// Choose the relay profile of the current account and optionally
+    /// publish its relays as the account's relay lists
+    async fn select_relay_profile(&mut self) -> Result<()> {
+        let account = if let Some(account) = self.account_manager.get_current_account() {
+            account.clone()
+        } else {
+            return Ok(());
+        };
+
+        let names = self.config.relay_profile_names();
+        let active = &self.relays.get_config().profile;
+        let options: Vec<String> = names.iter()
+            .map(|name| if name == active { format!("{} (active)", name) } else { name.clone() })
+            .collect();
+        let selection = Select::with_theme(&ColorfulTheme::default())
+            .with_prompt("Relay profile for this account:")
+            .items(&options)
+            .default(names.iter().position(|name| name == active).unwrap_or(0))
+            .interact()?;
+
+        let profile = self.config.relay_profile(&names[selection])?;
+        for relay_type in RelayManager::all_relay_types() {
+            println!("{} {}", style(format!("{}:", self.relays.relay_type_name(&relay_type))).bold(),
+                profile.relays_for_type(&relay_type).join(", "));
+        }
+
+        if Confirm::new()
+            .with_prompt("Also publish these relays as your account's relay lists?")
+            .default(false)
+            .interact()?
+        {
+            let mut account = account.clone();
+            let mut published = true;
+            for relay_type in RelayManager::all_relay_types() {
+                let relays = profile.relays_for_type(&relay_type).clone();
+                match self.relays.update_relays(&mut account, relay_type, relays).await {
+                    Ok(()) => println!("{} {} relays published", style("✅").green(), self.relays.relay_type_name(&relay_type)),
+                    Err(e) => {
+                        println!("{} {}", style("❌").red(), e);
+                        published = false;
+                    }
+                }
+            }
+            self.account_manager.set_current_account(account);
+            if !published {
+                println!("{}", style("Relay profile left unchanged.").yellow());
+                ui::wait_for_enter("Press Enter to continue...");
+                return Ok(());
+            }
+        }
+
+        self.storage.save_account_relay_profile(&account.pubkey.to_hex(), Some(&profile.profile)).await?;
+        self.apply_relay_profile().await?;
+        println!("{} Relay profile set to {}", style("✅").green(), style(&profile.profile).green());
+
+        ui::wait_for_enter("Press Enter to continue...");
+        Ok(())
+    }
+
     async fn view_current_relays(&mut self) -> Result<()> {
         if let Some(account) = self.account_manager.get_current_account() {
             println!("{}", style("📋 Current Relay Configuration").bold().cyan());
@@ -724,6 +1273,7 @@ impl App {
 
     async fn add_relay(&mut self) -> Result<()> {
         if let Some(account) = self.account_manager.get_current_account() {
+            let mut account = account.clone();
             println!("{}", style("➕ Add Relay").bold().green());
             println!();
 
@@ -739,9 +1289,13 @@ impl App {
                 .with_prompt("Relay URL (wss://...)")
                 .interact()?;
 
-            match self.relays.add_relay_to_type(account, relay_type, relay_url).await {
-                Ok(_) => {
-                    println!("{} Relay added successfully!", style("✅").green());
+            match self.relays.add_relay_to_type(&mut account, relay_type, relay_url).await {
+                Ok(true) => {
+                    println!("{} Relay added and relay list published!", style("✅").green());
+                    self.account_manager.set_current_account(account);
+                }
+                Ok(false) => {
+                    println!("{} Relay is already configured.", style("ℹ️").blue());
                 }
                 Err(e) => {
                     println!("{} Failed to add relay: {}", style("❌").red(), e);
@@ -754,7 +1308,51 @@ impl App {
     }
 
     async fn remove_relay(&mut self) -> Result<()> {
-        println!("{}", style("🗑️  Remove relay functionality not yet implemented").yellow());
+        if let Some(account) = self.account_manager.get_current_account() {
+            let mut account = account.clone();
+            println!("{}", style("🗑️  Remove Relay").bold().red());
+            println!();
+
+            let relay_type_options = vec!["Nostr", "Inbox", "KeyPackage"];
+            let type_selection = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("Select relay type:")
+                .items(&relay_type_options)
+                .interact()?;
+
+            let relay_type = RelayManager::all_relay_types()[type_selection];
+            let relays = RelayManager::account_relays(&account, &relay_type);
+
+            if relays.is_empty() {
+                println!("{}", style("No relays configured for this type.").dim());
+                ui::wait_for_enter("Press Enter to continue...");
+                return Ok(());
+            }
+
+            let selection = Select::with_theme(&ColorfulTheme::default())
+                .with_prompt("Select relay to remove:")
+                .items(&relays)
+                .interact()?;
+
+            let confirm = Confirm::new()
+                .with_prompt(format!("Remove '{}' and publish the updated relay list?", relays[selection]))
+                .default(false)
+                .interact()?;
+
+            if confirm {
+                match self.relays.remove_relay_from_type(&mut account, relay_type, &relays[selection]).await {
+                    Ok(_) => {
+                        println!("{} Relay removed and relay list published!", style("✅").green());
+                        self.account_manager.set_current_account(account);
+                    }
+                    Err(e) => {
+                        println!("{} Failed to remove relay: {}", style("❌").red(), e);
+                    }
+                }
+            } else {
+                println!("Cancelled.");
+            }
+        }
+
         ui::wait_for_enter("Press Enter to continue...");
         Ok(())
     }
@@ -783,6 +1381,7 @@ impl App {
                 "📝 Update Profile",
                 "📋 Export Public Key (npub)",
                 "🔐 Export Private Key (nsec)",
+                "🔄 Switch Account",
                 "🚪 Logout",
                 "🔙 Back to Main Menu",
             ];
@@ -797,10 +1396,17 @@ impl App {
                 1 => self.export_public_key().await?,
                 2 => self.export_private_key().await?,
                 3 => {
+                    // Back to the main menu so it shows the new account
+                    let switched = self.switch_account().await?;
+                    if switched {
+                        return Ok(true);
+                    }
+                }
+                4 => {
                     self.account_manager.logout().await?;
                     return Ok(true);
                 }
-                4 => return Ok(true),
+                5 => return Ok(true),
                 _ => {}
             }
         }
@@ -894,35 +1500,16 @@ impl App {
         Ok(())
     }
 
-    /// Auto-login with a specific account by public key
-    pub async fn auto_login_by_pubkey(&mut self, pubkey_hex: &str) -> Result<()> {
-        // Parse the pubkey
-        let pubkey = PublicKey::from_hex(pubkey_hex)
-            .map_err(|e| anyhow::anyhow!("Invalid public key hex: {}", e))?;
-        
-        // Fetch all accounts to find the matching one
-        let accounts = self.account_manager.fetch_accounts().await?;
-        
-        // Find the account with matching pubkey
-        let matching_account = accounts.iter()
-            .find(|acc| acc.pubkey == pubkey.to_hex());
-        
-        if matching_account.is_none() {
-            return Err(anyhow::anyhow!("No account found with pubkey: {}", pubkey_hex));
-        }
-        
-        // Get the account from WhiteNoise database
+    /// Auto-login with a specific account given as hex, npub, nprofile,
+    /// NIP-05 identifier or local profile name
+    pub async fn auto_login_by_pubkey(&mut self, identifier: &str) -> Result<()> {
+        let mut account = self.account_manager.resolve_account(identifier).await?;
+        // Checked before anything below can reach the account's relays
+        Self::check_relays_of(&account, "run `relay profile use local-dev --publish` once without --offline")?;
+
         let whitenoise = Whitenoise::get_instance()
             .map_err(|e| anyhow::anyhow!("Failed to get WhiteNoise instance: {:?}", e))?;
         
-        // Fetch all accounts and find the one with matching pubkey
-        let accounts = whitenoise.fetch_accounts().await
-            .map_err(|e| anyhow::anyhow!("Failed to fetch accounts: {:?}", e))?;
-        
-        let mut account = accounts.get(&pubkey)
-            .ok_or_else(|| anyhow::anyhow!("No account found with pubkey: {}", pubkey_hex))?
-            .clone();
-        
         // Fix empty relay arrays if present (needed for accounts affected by DB migration)
         if let Ok(_) = whitenoise.fix_account_empty_relays(&mut account).await {
             // Silent fix for CLI operations
@@ -0,0 +1,350 @@
+use anyhow::Result;
+use console::style;
+use crossterm::{
+    cursor,
+    event::{self, Event, KeyCode, KeyEvent, KeyEventKind, KeyModifiers},
+    queue,
+    style::Print,
+    terminal::{self, ClearType},
+};
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc,
+};
+use std::time::Duration;
+use tokio::sync::mpsc;
+use whitenoise::{Account, ChatMessage, GroupId};
+
+use crate::groups::{GroupManager, MessageFeed, OutgoingMessage, FEED_POLL_INTERVAL};
+
+/// Rows taken by the header, its separator, the status line and the input line
+const CHROME_ROWS: u16 = 4;
+
+enum ChatEvent {
+    Key(KeyEvent),
+    Resize,
+    Messages(Vec<ChatMessage>),
+    Sent(Result<()>),
+    FeedError(String),
+}
+
+/// Restores the terminal when the chat view exits, including on error
+struct TerminalGuard;
+
+impl TerminalGuard {
+    fn enter() -> Result<Self> {
+        terminal::enable_raw_mode()?;
+        let mut stdout = io::stdout();
+        queue!(stdout, terminal::EnterAlternateScreen, cursor::Show)?;
+        stdout.flush()?;
+        Ok(Self)
+    }
+}
+
+impl Drop for TerminalGuard {
+    fn drop(&mut self) {
+        let mut stdout = io::stdout();
+        let _ = queue!(stdout, terminal::LeaveAlternateScreen, cursor::Show);
+        let _ = stdout.flush();
+        let _ = terminal::disable_raw_mode();
+    }
+}
+
+/// Full-screen chat for one group. Incoming messages are delivered by a
+/// background poller and rendered as they arrive; the input line is
+/// independent of the message pane, which keeps its scrollback.
+pub struct ChatView {
+    title: String,
+    own_pubkey: String,
+    labels: HashMap<String, String>,
+    messages: Vec<ChatMessage>,
+    input: String,
+    /// Rows scrolled up from the newest message
+    scroll: usize,
+    status: Option<String>,
+}
+
+impl ChatView {
+    pub fn new(title: &str, own_pubkey: String, labels: HashMap<String, String>) -> Self {
+        Self {
+            title: title.to_string(),
+            own_pubkey,
+            labels,
+            messages: Vec::new(),
+            input: String::new(),
+            scroll: 0,
+            status: None,
+        }
+    }
+
+    pub async fn run(mut self, account: &Account, group_id: &GroupId) -> Result<()> {
+        let (tx, mut rx) = mpsc::unbounded_channel();
+        let stop = Arc::new(AtomicBool::new(false));
+
+        // Raw mode first, so no keystroke is read or echoed in cooked mode
+        let _guard = TerminalGuard::enter()?;
+        let input_thread = spawn_input_reader(tx.clone(), stop.clone());
+        let poller = spawn_message_poller(tx.clone(), account.clone(), group_id.clone());
+        self.render()?;
+
+        while let Some(event) = rx.recv().await {
+            match event {
+                ChatEvent::Key(key) => {
+                    if !self.handle_key(key, account, group_id, &tx) {
+                        break;
+                    }
+                }
+                ChatEvent::Resize => {}
+                ChatEvent::Messages(messages) => {
+                    // Keep the viewport anchored while the user reads scrollback
+                    let width = terminal::size().map(|(cols, _)| cols as usize).unwrap_or(80);
+                    let before = self.message_rows(width).len();
+                    self.messages.extend(messages);
+                    if self.scroll > 0 {
+                        self.scroll += self.message_rows(width).len() - before;
+                    }
+                }
+                ChatEvent::Sent(Ok(())) => self.status = None,
+                ChatEvent::Sent(Err(e)) => self.status = Some(format!("Failed to send message: {}", e)),
+                ChatEvent::FeedError(e) => self.status = Some(format!("Failed to fetch messages: {}", e)),
+            }
+            self.render()?;
+        }
+
+        stop.store(true, Ordering::Relaxed);
+        poller.abort();
+        let _ = input_thread.join();
+        Ok(())
+    }
+
+    /// Returns false when the user asked to leave the chat
+    fn handle_key(
+        &mut self,
+        key: KeyEvent,
+        account: &Account,
+        group_id: &GroupId,
+        tx: &mpsc::UnboundedSender<ChatEvent>,
+    ) -> bool {
+        if key.kind == KeyEventKind::Release {
+            return true;
+        }
+
+        match key.code {
+            KeyCode::Esc => return false,
+            KeyCode::Char('c') | KeyCode::Char('d') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                return false;
+            }
+            KeyCode::Enter => {
+                let content = std::mem::take(&mut self.input);
+                let content = content.trim();
+                if content == "/quit" {
+                    return false;
+                }
+                if !content.is_empty() {
+                    self.scroll = 0;
+                    self.status = Some("Sending...".to_string());
+                    spawn_send(tx.clone(), account.clone(), group_id.clone(), content.to_string());
+                }
+            }
+            KeyCode::Backspace => {
+                self.input.pop();
+            }
+            KeyCode::Char(c) => self.input.push(c),
+            KeyCode::Up => self.scroll += 1,
+            KeyCode::Down => self.scroll = self.scroll.saturating_sub(1),
+            KeyCode::PageUp => self.scroll += self.pane_height(),
+            KeyCode::PageDown => self.scroll = self.scroll.saturating_sub(self.pane_height()),
+            KeyCode::End => self.scroll = 0,
+            _ => {}
+        }
+        true
+    }
+
+    fn pane_height(&self) -> usize {
+        let (_, rows) = terminal::size().unwrap_or((80, 24));
+        rows.saturating_sub(CHROME_ROWS) as usize
+    }
+
+    fn label(&self, pubkey: &str) -> String {
+        if pubkey == self.own_pubkey {
+            return "you".to_string();
+        }
+        self.labels
+            .get(pubkey)
+            .cloned()
+            .unwrap_or_else(|| pubkey[..8].to_string())
+    }
+
+    /// Message pane rows, wrapped to `width` and styled
+    fn message_rows(&self, width: usize) -> Vec<String> {
+        let mut rows = Vec::new();
+        for msg in &self.messages {
+            let timestamp = chrono::DateTime::from_timestamp(msg.created_at.as_u64() as i64, 0)
+                .unwrap_or_default()
+                .format("%H:%M");
+            let author = msg.author.to_hex();
+            let prefix = format!("[{}] {}: ", timestamp, self.label(&author));
+            let indent = prefix.chars().count().min(width / 2);
+
+            let mut chunks = wrap(&msg.content, width.saturating_sub(indent).max(1)).into_iter();
+            let first = chunks.next().unwrap_or_default();
+            let author_style = if author == self.own_pubkey {
+                style(format!("{}:", self.label(&author))).bold().green()
+            } else {
+                style(format!("{}:", self.label(&author))).bold().blue()
+            };
+            rows.push(format!("{} {} {}", style(format!("[{}]", timestamp)).dim(), author_style, first));
+            rows.extend(chunks.map(|chunk| format!("{}{}", " ".repeat(indent), chunk)));
+        }
+        rows
+    }
+
+    fn render(&mut self) -> Result<()> {
+        let (cols, rows) = terminal::size()?;
+        let width = cols as usize;
+        let pane = rows.saturating_sub(CHROME_ROWS) as usize;
+
+        let all_rows = self.message_rows(width);
+        self.scroll = self.scroll.min(all_rows.len().saturating_sub(pane));
+        let end = all_rows.len() - self.scroll;
+        let start = end.saturating_sub(pane);
+
+        let mut stdout = io::stdout();
+        queue!(stdout, terminal::Clear(ClearType::All), cursor::MoveTo(0, 0))?;
+
+        let scrolled = if self.scroll > 0 { format!(" (↑{} more below)", self.scroll) } else { String::new() };
+        queue!(
+            stdout,
+            Print(style(format!("💬 Group Chat: {}", self.title)).bold().cyan()),
+            Print(style(scrolled).dim()),
+            cursor::MoveTo(0, 1),
+            Print(style("─".repeat(width)).dim())
+        )?;
+
+        if all_rows.is_empty() {
+            queue!(stdout, cursor::MoveTo(0, 2), Print(style("No messages yet. Start the conversation!").dim().italic()))?;
+        }
+        for (i, row) in all_rows[start..end].iter().enumerate() {
+            queue!(stdout, cursor::MoveTo(0, 2 + i as u16), Print(row))?;
+        }
+
+        let footer = self.status.clone().unwrap_or_else(|| {
+            "Enter to send · PgUp/PgDn to scroll · Esc or /quit to leave".to_string()
+        });
+        let prompt = "💭 ";
+        let visible_input: String = {
+            let max = width.saturating_sub(3);
+            let skip = self.input.chars().count().saturating_sub(max);
+            self.input.chars().skip(skip).collect()
+        };
+        queue!(
+            stdout,
+            cursor::MoveTo(0, rows.saturating_sub(2)),
+            Print(style(footer).dim()),
+            cursor::MoveTo(0, rows.saturating_sub(1)),
+            Print(prompt),
+            Print(&visible_input),
+            cursor::MoveTo(3 + visible_input.chars().count() as u16, rows.saturating_sub(1))
+        )?;
+        stdout.flush()?;
+        Ok(())
+    }
+}
+
+/// Split `text` into rows of at most `width` characters, breaking on spaces where possible
+fn wrap(text: &str, width: usize) -> Vec<String> {
+    let mut rows = Vec::new();
+    for line in text.lines() {
+        let mut current = String::new();
+        for word in line.split(' ') {
+            let needed = if current.is_empty() { word.chars().count() } else { current.chars().count() + 1 + word.chars().count() };
+            if needed > width && !current.is_empty() {
+                rows.push(std::mem::take(&mut current));
+            }
+            if !current.is_empty() {
+                current.push(' ');
+            }
+            current.push_str(word);
+            while current.chars().count() > width {
+                let head: String = current.chars().take(width).collect();
+                current = current.chars().skip(width).collect();
+                rows.push(head);
+            }
+        }
+        rows.push(current);
+    }
+    if rows.is_empty() {
+        rows.push(String::new());
+    }
+    rows
+}
+
+fn spawn_input_reader(tx: mpsc::UnboundedSender<ChatEvent>, stop: Arc<AtomicBool>) -> std::thread::JoinHandle<()> {
+    std::thread::spawn(move || {
+        while !stop.load(Ordering::Relaxed) {
+            if !event::poll(Duration::from_millis(100)).unwrap_or(false) {
+                continue;
+            }
+            let forwarded = match event::read() {
+                Ok(Event::Key(key)) => tx.send(ChatEvent::Key(key)),
+                Ok(Event::Resize(_, _)) => tx.send(ChatEvent::Resize),
+                Ok(_) => Ok(()),
+                Err(_) => break,
+            };
+            if forwarded.is_err() {
+                break;
+            }
+        }
+    })
+}
+
+/// whitenoise has no message subscription; each tick is a cheap group list
+/// read, and the history is only fetched again once a new message landed
+fn spawn_message_poller(
+    tx: mpsc::UnboundedSender<ChatEvent>,
+    account: Account,
+    group_id: GroupId,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let groups = GroupManager::new();
+        let mut feed = MessageFeed::new();
+        let mut ticker = tokio::time::interval(FEED_POLL_INTERVAL);
+        loop {
+            ticker.tick().await;
+            let event = match feed.poll(&groups, &account, &group_id).await {
+                Ok(messages) if messages.is_empty() => continue,
+                Ok(messages) => ChatEvent::Messages(messages),
+                Err(e) => ChatEvent::FeedError(e.to_string()),
+            };
+            if tx.send(event).is_err() {
+                break;
+            }
+        }
+    })
+}
+
+fn spawn_send(tx: mpsc::UnboundedSender<ChatEvent>, account: Account, group_id: GroupId, content: String) {
+    tokio::spawn(async move {
+        let result = GroupManager::new()
+            .send_message_to_group(&account, &group_id, OutgoingMessage::text(content))
+            .await
+            .map(|_| ());
+        let _ = tx.send(ChatEvent::Sent(result));
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(wrap("hello world", 20), vec!["hello world"]);
+        assert_eq!(wrap("hello world", 7), vec!["hello", "world"]);
+        assert_eq!(wrap("abcdefghij", 4), vec!["abcd", "efgh", "ij"]);
+        assert_eq!(wrap("one\ntwo", 10), vec!["one", "two"]);
+        assert_eq!(wrap("", 10), vec![""]);
+    }
+}
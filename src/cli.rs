@@ -1,6 +1,10 @@
-use clap::{Parser, Subcommand};
+use clap::{Args, Parser, Subcommand};
 use serde::{Deserialize, Serialize};
 
+use crate::errors::CliError;
+use crate::export::ExportFormat;
+use crate::groups::GroupFilter;
+
 #[derive(Parser)]
 #[command(name = "whitenoise-cli")]
 #[command(about = "WhiteNoise CLI - Secure MLS messaging client")]
@@ -10,9 +14,9 @@ pub struct Cli {
     #[arg(short, long)]
     pub interactive: bool,
 
-    /// Output format
-    #[arg(short, long, value_enum, default_value = "human")]
-    pub output: OutputFormat,
+    /// Output format (default: human, or `output` from the config file)
+    #[arg(short, long, value_enum)]
+    pub output: Option<OutputFormat>,
 
     /// Suppress all output except results
     #[arg(short, long)]
@@ -22,15 +26,28 @@ pub struct Cli {
     #[arg(short, long)]
     pub config: Option<String>,
 
-    /// Account public key to use (hex format)
+    /// Account to use: hex, npub, nprofile, NIP-05 identifier or profile name
     #[arg(short = 'a', long)]
     pub account: Option<String>,
 
+    /// Relay profile for this run: default, local-dev, public or a custom one from the config
+    #[arg(long)]
+    pub relay_profile: Option<String>,
+
+    /// Only talk to the local development relay (ws://localhost:10547), or to --relays
+    #[arg(long)]
+    pub offline: bool,
+
+    /// Restrict the CLI's networking to these relays: 'local' or comma-separated URLs
+    #[arg(long, conflicts_with = "relay_profile")]
+    pub relays: Option<String>,
+
     #[command(subcommand)]
     pub command: Option<Commands>,
 }
 
-#[derive(Clone, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum)]
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum OutputFormat {
     Human,
     Json,
@@ -59,6 +76,11 @@ pub enum Commands {
         #[command(subcommand)]
         command: MessageCommands,
     },
+    /// Group invitation commands
+    Invite {
+        #[command(subcommand)]
+        command: InviteCommands,
+    },
     /// Relay management commands
     Relay {
         #[command(subcommand)]
@@ -77,30 +99,91 @@ pub enum Commands {
         #[command(subcommand)]
         command: KeysCommands,
     },
+    /// Browse conversation archives imported from other installations
+    History {
+        #[command(subcommand)]
+        command: HistoryCommands,
+    },
+    /// Inspect the loaded configuration
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+}
+
+impl Commands {
+    /// Whether the command publishes or subscribes through the current
+    /// account's relays; purely local reads do not
+    pub fn uses_account_relays(&self) -> bool {
+        match self {
+            Commands::Contact { command } => matches!(command, ContactCommands::Add { .. } | ContactCommands::Fetch),
+            Commands::Group { command } => !matches!(command,
+                GroupCommands::List { .. } | GroupCommands::Show { .. } | GroupCommands::Members { .. }
+                    | GroupCommands::Admins { .. } | GroupCommands::Export { .. }
+                    | GroupCommands::Archive { .. } | GroupCommands::Unarchive { .. }),
+            Commands::Message { command } => !matches!(command,
+                MessageCommands::List { .. } | MessageCommands::ListDm { .. } | MessageCommands::GetDmGroup { .. }
+                    | MessageCommands::Search { .. } | MessageCommands::Download { .. }),
+            Commands::Invite { command } => matches!(command, InviteCommands::Accept { .. }),
+            Commands::Batch { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommands {
+    /// Show the effective configuration and where it was loaded from
+    Show,
+}
+
+#[derive(Subcommand)]
+pub enum HistoryCommands {
+    /// Verify and import an archive written by `group export --format json`
+    Import {
+        /// Path to the archive file
+        file: String,
+    },
+    /// List imported archives
+    List,
+    /// Show the messages of an imported archive (read-only)
+    Show {
+        /// Archive ID from `history list`
+        archive_id: String,
+        /// Only show the last N messages
+        #[arg(short, long)]
+        limit: Option<usize>,
+    },
 }
 
 #[derive(Subcommand)]
 pub enum KeysCommands {
     /// Store a private key locally
     Store {
-        /// Public key (hex)
+        /// Public key (hex, npub, nprofile, NIP-05 or contact name)
         #[arg(short, long)]
         pubkey: String,
         /// Private key (nsec or hex)
         #[arg(short = 'k', long)]
         privkey: String,
+        /// Read the key store passphrase from stdin instead of prompting
+        #[arg(long)]
+        passphrase_stdin: bool,
     },
     /// Retrieve a stored private key
     Get {
-        /// Public key (hex)
+        /// Public key (hex, npub, nprofile, NIP-05 or contact name)
         #[arg(short, long)]
         pubkey: String,
+        /// Read the key store passphrase from stdin instead of prompting
+        #[arg(long)]
+        passphrase_stdin: bool,
     },
     /// List all stored public keys
     List,
     /// Remove a stored key
     Remove {
-        /// Public key (hex)
+        /// Public key (hex, npub, nprofile, NIP-05 or contact name)
         #[arg(short, long)]
         pubkey: String,
     },
@@ -124,7 +207,16 @@ pub enum AccountCommands {
         key: String,
     },
     /// List all accounts
-    List,
+    List {
+        /// Count unread group messages for each account
+        #[arg(short, long)]
+        unread: bool,
+    },
+    /// Switch the current account to another logged-in one
+    Switch {
+        /// Account npub, hex public key or profile name
+        account: String,
+    },
     /// Show current account info
     Info,
     /// Export public key
@@ -150,7 +242,7 @@ pub enum AccountCommands {
 pub enum ContactCommands {
     /// Add a contact
     Add {
-        /// Contact's public key (npub or hex)
+        /// Contact's public key (npub, nprofile, hex or NIP-05)
         #[arg(short, long)]
         pubkey: String,
         /// Display name
@@ -159,7 +251,7 @@ pub enum ContactCommands {
     },
     /// Remove a contact
     Remove {
-        /// Contact's public key (npub or hex)
+        /// Contact's public key (npub, nprofile, hex, NIP-05 or contact name)
         #[arg(short, long)]
         pubkey: String,
     },
@@ -169,7 +261,7 @@ pub enum ContactCommands {
     Fetch,
     /// Show contact details
     Show {
-        /// Contact's public key (npub or hex)
+        /// Contact's public key (npub, nprofile, hex, NIP-05 or contact name)
         pubkey: String,
     },
 }
@@ -184,21 +276,116 @@ pub enum GroupCommands {
         /// Group description
         #[arg(short, long)]
         description: Option<String>,
-        /// Member public keys (comma-separated)
+        /// Member public keys (comma-separated npub, nprofile, hex, NIP-05 or contact names)
         #[arg(short, long)]
         members: Option<String>,
     },
-    /// List all groups
-    List,
+    /// List groups (archived groups are hidden unless --state archived)
+    List {
+        /// Only list groups in this state
+        #[arg(short, long, value_enum)]
+        state: Option<GroupFilter>,
+    },
     /// Show group details
     Show {
         /// Group ID
         group_id: String,
     },
-    /// Join a group chat (interactive)
+    /// Join a group chat: stream incoming messages as NDJSON and send stdin lines
     Join {
         /// Group ID
         group_id: String,
+        /// Number of recent messages to emit before streaming (default: 0)
+        #[arg(long, default_value = "0")]
+        history: usize,
+    },
+    /// Add members to a group (admin only)
+    AddMember {
+        /// Group ID
+        group_id: String,
+        /// Member public keys (comma-separated npub, nprofile, hex, NIP-05 or contact names)
+        #[arg(short, long)]
+        members: String,
+    },
+    /// Remove members from a group (admin only)
+    RemoveMember {
+        /// Group ID
+        group_id: String,
+        /// Member public keys (comma-separated npub, nprofile, hex, NIP-05 or contact names)
+        #[arg(short, long)]
+        members: String,
+    },
+    /// List group members
+    Members {
+        /// Group ID
+        group_id: String,
+    },
+    /// List group admins
+    Admins {
+        /// Group ID
+        group_id: String,
+    },
+    /// Promote a member to admin (admin only)
+    Promote {
+        /// Group ID
+        group_id: String,
+        /// Member public key (npub, nprofile, hex, NIP-05 or contact name)
+        #[arg(short, long)]
+        member: String,
+    },
+    /// Demote an admin to a regular member (admin only)
+    Demote {
+        /// Group ID
+        group_id: String,
+        /// Member public key (npub, nprofile, hex, NIP-05 or contact name)
+        #[arg(short, long)]
+        member: String,
+    },
+    /// Export a group's decrypted history
+    Export {
+        /// Group ID
+        group_id: String,
+        /// json writes a signed archive that `history import` can read
+        #[arg(short, long, value_enum, default_value = "json")]
+        format: ExportFormat,
+        /// File to write (default: <group name>.<format extension>)
+        #[arg(long)]
+        file: Option<String>,
+    },
+    /// Leave a group; other members see you removed
+    Leave {
+        /// Group ID
+        group_id: String,
+    },
+    /// Hide a group from listings on this machine
+    Archive {
+        /// Group ID
+        group_id: String,
+    },
+    /// Show an archived group in listings again
+    Unarchive {
+        /// Group ID
+        group_id: String,
+    },
+    /// Change a group's name, description, image or relays (admin only)
+    Update {
+        /// Group ID
+        group_id: String,
+        /// New group name
+        #[arg(short, long)]
+        name: Option<String>,
+        /// New group description
+        #[arg(short, long)]
+        description: Option<String>,
+        /// URL of the new group image
+        #[arg(short, long, conflicts_with = "clear_image")]
+        image: Option<String>,
+        /// Remove the group image
+        #[arg(long)]
+        clear_image: bool,
+        /// Relays the group publishes to (comma-separated, replaces the current list)
+        #[arg(short, long)]
+        relays: Option<String>,
     },
 }
 
@@ -212,13 +399,13 @@ pub enum MessageCommands {
         /// Message content
         #[arg(short, long)]
         message: String,
-        /// Message kind (default: 1)
-        #[arg(short, long, default_value = "1")]
-        kind: u16,
+        /// Message kind: 9 (chat, default) or 1 (text note)
+        #[arg(short, long)]
+        kind: Option<u16>,
     },
     /// Send a direct message (creates/uses MLS DM group)
     Dm {
-        /// Recipient's public key (npub or hex)
+        /// Recipient's public key (npub, nprofile, hex, NIP-05 or contact name)
         #[arg(short, long)]
         recipient: String,
         /// Message content
@@ -230,25 +417,183 @@ pub enum MessageCommands {
         /// Group ID
         #[arg(short, long)]
         group_id: String,
-        /// Number of messages to fetch (default: 20)
-        #[arg(short, long, default_value = "20")]
-        limit: usize,
+        #[command(flatten)]
+        page: PageArgs,
+        /// Mark the listed messages as read for `account list --unread`
+        #[arg(long)]
+        mark_read: bool,
     },
     /// List direct messages with a contact
     ListDm {
-        /// Contact's public key (npub or hex)
+        /// Contact's public key (npub, nprofile, hex, NIP-05 or contact name)
         #[arg(short, long)]
         contact: String,
-        /// Number of messages to fetch (default: 20)
-        #[arg(short, long, default_value = "20")]
-        limit: usize,
+        #[command(flatten)]
+        page: PageArgs,
+        /// Mark the listed messages as read for `account list --unread`
+        #[arg(long)]
+        mark_read: bool,
     },
     /// Get or create DM group with a contact
     GetDmGroup {
-        /// Contact's public key (npub or hex)
+        /// Contact's public key (npub, nprofile, hex, NIP-05 or contact name)
         #[arg(short, long)]
         contact: String,
     },
+    /// Reply to a message in a group
+    Reply {
+        /// Group ID
+        #[arg(short, long)]
+        group_id: String,
+        /// ID (or unique prefix) of the message to reply to
+        #[arg(short = 'i', long)]
+        message_id: String,
+        /// Message content
+        #[arg(short, long)]
+        message: String,
+    },
+    /// React to a message in a group
+    React {
+        /// Group ID
+        #[arg(short, long)]
+        group_id: String,
+        /// ID (or unique prefix) of the message to react to
+        #[arg(short = 'i', long)]
+        message_id: String,
+        /// Reaction content, usually an emoji (default: +)
+        #[arg(short, long, default_value = "+")]
+        emoji: String,
+    },
+    /// Delete one of your messages in a group
+    Delete {
+        /// Group ID
+        #[arg(short, long)]
+        group_id: String,
+        /// ID (or unique prefix) of the message to delete
+        #[arg(short = 'i', long)]
+        message_id: String,
+        /// Optional reason shown to other clients
+        #[arg(short, long)]
+        reason: Option<String>,
+    },
+    /// Encrypt a file, upload it and send it to a group
+    SendFile {
+        /// Group ID
+        #[arg(short, long)]
+        group_id: String,
+        /// Path of the file to send
+        #[arg(short, long)]
+        file: String,
+        /// Optional caption sent with the file
+        #[arg(short, long)]
+        caption: Option<String>,
+        /// Blossom server to upload to (default: configured media server)
+        #[arg(long)]
+        server: Option<String>,
+    },
+    /// Download and decrypt the attachments of a message
+    Download {
+        /// Group ID
+        #[arg(short, long)]
+        group_id: String,
+        /// ID (or unique prefix) of the message with attachments
+        #[arg(short = 'i', long)]
+        message_id: String,
+        /// Directory to save into (default: current directory)
+        #[arg(short, long)]
+        output_dir: Option<String>,
+    },
+    /// Search message history across all groups
+    Search {
+        /// Words to look for (all must match, prefixes allowed), or a regex with --regex
+        query: String,
+        /// Treat the query as a case-insensitive regular expression
+        #[arg(long)]
+        regex: bool,
+        /// Only search this group
+        #[arg(short, long)]
+        group_id: Option<String>,
+        /// Only messages from this author (npub, nprofile, hex, NIP-05 or contact name)
+        #[arg(short, long)]
+        author: Option<String>,
+        /// Only messages of this kind
+        #[arg(short, long)]
+        kind: Option<u16>,
+        /// Only messages at or after this date (YYYY-MM-DD or unix timestamp)
+        #[arg(long)]
+        since: Option<String>,
+        /// Only messages at or before this date (YYYY-MM-DD or unix timestamp)
+        #[arg(long)]
+        until: Option<String>,
+        /// Maximum number of results
+        #[arg(short, long, default_value = "50")]
+        limit: usize,
+        /// Search the local index without checking groups for new messages
+        #[arg(long)]
+        no_sync: bool,
+    },
+    /// Follow new messages across all groups as NDJSON
+    Watch {
+        /// Only watch this group
+        #[arg(short, long)]
+        group_id: Option<String>,
+        /// Only emit messages from this author (npub, nprofile, hex, NIP-05 or contact name)
+        #[arg(short, long)]
+        sender: Option<String>,
+        /// Also emit stored messages created at or after this unix timestamp
+        #[arg(long)]
+        since: Option<u64>,
+        /// Watch these logged-in accounts instead of the current one (comma-separated npubs or names)
+        #[arg(long, conflicts_with = "all_accounts")]
+        accounts: Option<String>,
+        /// Watch every logged-in account
+        #[arg(long)]
+        all_accounts: bool,
+    },
+}
+
+/// Which slice of a message history to list. Pages run from the newest
+/// message backwards; pass `next_cursor` from the envelope to get the next one.
+#[derive(Args, Debug, Clone, Default)]
+pub struct PageArgs {
+    /// Number of messages per page (default: 20)
+    #[arg(short, long, default_value = "20")]
+    pub limit: usize,
+    /// Skip this many of the newest matching messages
+    #[arg(long, default_value = "0", conflicts_with = "cursor")]
+    pub offset: usize,
+    /// Only messages created before this time (YYYY-MM-DD or unix timestamp)
+    #[arg(long)]
+    pub before: Option<String>,
+    /// Only messages created at or after this time (YYYY-MM-DD or unix timestamp)
+    #[arg(long)]
+    pub after: Option<String>,
+    /// Only messages sent after the message with this ID
+    #[arg(long)]
+    pub since_id: Option<String>,
+    /// Continue from the `next_cursor` of a previous page
+    #[arg(long)]
+    pub cursor: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum InviteCommands {
+    /// List pending group invitations
+    List {
+        /// Include accepted, declined and ignored invitations
+        #[arg(long)]
+        all: bool,
+    },
+    /// Accept an invitation and join the group
+    Accept {
+        /// Invite ID (or unique prefix)
+        invite_id: String,
+    },
+    /// Decline an invitation
+    Decline {
+        /// Invite ID (or unique prefix)
+        invite_id: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -282,6 +627,28 @@ pub enum RelayCommands {
         /// Relay URL
         url: String,
     },
+    /// Manage named relay profiles
+    Profile {
+        #[command(subcommand)]
+        command: RelayProfileCommands,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum RelayProfileCommands {
+    /// List built-in and custom relay profiles
+    List,
+    /// Show the relays of a profile (the active one by default)
+    Show {
+        name: Option<String>,
+    },
+    /// Use a profile for the current account
+    Use {
+        name: String,
+        /// Also publish the profile's relays as the account's relay lists
+        #[arg(long)]
+        publish: bool,
+    },
 }
 
 #[derive(Serialize, Deserialize)]
@@ -289,6 +656,20 @@ pub struct BatchOperation {
     pub operations: Vec<BatchCommand>,
 }
 
+impl BatchOperation {
+    /// Parse a batch file, choosing JSON or YAML by its extension
+    pub fn parse(content: &str, path: &std::path::Path) -> anyhow::Result<Self> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Ok(serde_json::from_str(content)?),
+            Some("yaml") | Some("yml") => Ok(serde_yaml::from_str(content)?),
+            _ => Err(anyhow::anyhow!(
+                "Unsupported batch file {}: use a .json, .yaml or .yml extension",
+                path.display()
+            )),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 #[serde(tag = "command")]
 pub enum BatchCommand {
@@ -305,25 +686,96 @@ pub struct CommandResult<T> {
     pub success: bool,
     pub data: Option<T>,
     pub error: Option<String>,
+    /// Stable error code (see `CliError::code`) when `success` is false
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<String>,
+    /// Paging state for paginated listings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub page: Option<PageInfo>,
     pub timestamp: chrono::DateTime<chrono::Utc>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PageInfo {
+    /// Messages matching the filters, across all pages
+    pub total: usize,
+    pub has_more: bool,
+    /// Pass as `--cursor` to fetch the next (older) page
+    pub next_cursor: Option<String>,
+}
+
 impl<T> CommandResult<T> {
     pub fn success(data: T) -> Self {
         Self {
             success: true,
             data: Some(data),
             error: None,
+            error_code: None,
+            page: None,
             timestamp: chrono::Utc::now(),
         }
     }
 
+    pub fn with_page(self, page: PageInfo) -> Self {
+        Self { page: Some(page), ..self }
+    }
+
     pub fn error(error: String) -> Self {
         Self {
             success: false,
             data: None,
             error: Some(error),
+            error_code: None,
+            page: None,
             timestamp: chrono::Utc::now(),
         }
     }
-}
\ No newline at end of file
+
+    pub fn failure(error: &CliError) -> Self {
+        Self {
+            error_code: Some(error.code().to_string()),
+            ..Self::error(error.to_string())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    #[test]
+    fn test_parse_yaml_batch() {
+        let yaml = r#"
+operations:
+  - command: ContactAdd
+    pubkey: abcd
+    name: Alice
+  - command: MessageSend
+    group_id: "0011"
+    message: hello
+"#;
+        let batch = BatchOperation::parse(yaml, Path::new("ops.yaml")).unwrap();
+        assert_eq!(batch.operations.len(), 2);
+        assert!(matches!(
+            &batch.operations[1],
+            BatchCommand::MessageSend { kind: None, message, .. } if message == "hello"
+        ));
+
+        assert!(BatchOperation::parse(yaml, Path::new("ops.txt")).is_err());
+    }
+
+    #[test]
+    fn test_uses_account_relays() {
+        let uses = |args: &[&str]| {
+            let cli = Cli::try_parse_from(std::iter::once("whitenoise-cli").chain(args.iter().copied())).unwrap();
+            cli.command.unwrap().uses_account_relays()
+        };
+        assert!(uses(&["message", "send", "--group-id", "00", "--message", "hi"]));
+        assert!(uses(&["contact", "fetch"]));
+        assert!(!uses(&["message", "list", "--group-id", "00"]));
+        assert!(!uses(&["group", "list"]));
+        assert!(!uses(&["invite", "list"]));
+        assert!(!uses(&["relay", "list"]));
+    }
+}
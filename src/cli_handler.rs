@@ -1,16 +1,26 @@
 use anyhow::Result;
 use serde_json;
-use std::collections::HashMap;
-use whitenoise::{PublicKey, RelayType, Metadata};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use whitenoise::{PublicKey, RelayType, Metadata, NostrGroupDataUpdate};
 
 use crate::{
     app::App,
     cli::{
-        AccountCommands, ContactCommands, GroupCommands, MessageCommands, RelayCommands,
-        Commands, CommandResult, OutputFormat, BatchOperation, BatchCommand, KeysCommands
+        AccountCommands, ContactCommands, GroupCommands, InviteCommands, MessageCommands, RelayCommands,
+        Commands, CommandResult, OutputFormat, BatchOperation, BatchCommand, KeysCommands,
+        ConfigCommands, HistoryCommands, RelayProfileCommands
     },
+    config::Config,
+    errors::{CliError, FailureWithData},
+    groups::{paginate_messages, MessageKind, OutgoingMessage},
+    export::{ConversationArchive, ExportFormat},
+    identifiers,
+    media::{MediaAttachment, MediaManager},
+    search::{parse_time_bound, SearchHit, SearchQuery},
+    output::{HumanRenderer, View},
     whitenoise_config::WhitenoiseManager,
-    keyring_helper::{KeyringHelper, setup_keyring_environment},
+    keyring_helper::{KeyringHelper, read_passphrase, setup_keyring_environment},
 };
 
 pub struct CliHandler {
@@ -21,24 +31,42 @@ pub struct CliHandler {
 }
 
 impl CliHandler {
-    pub async fn new(output_format: OutputFormat, quiet: bool, account_pubkey: Option<String>) -> Result<Self> {
+    pub async fn new(
+        config: Config,
+        output_format: OutputFormat,
+        quiet: bool,
+        account_pubkey: Option<String>,
+        relay_profile: Option<String>,
+    ) -> Result<Self> {
         // Initialize WhiteNoise in quiet mode for CLI
         // Completely suppress nostr_relay_pool errors which include purplepag.es timeouts
-        std::env::set_var("RUST_LOG", "whitenoise=error,nostr_relay_pool=off");
+        std::env::set_var("RUST_LOG", config.log_filter_or("whitenoise=error,nostr_relay_pool=off"));
         
         // Setup keyring environment for keyring-less operation
         setup_keyring_environment()?;
         
-        let whitenoise_manager = WhitenoiseManager::new()?;
+        let whitenoise_manager = WhitenoiseManager::new(&config)?;
         let mut manager = whitenoise_manager;
         manager.initialize().await?;
         
-        let mut app = App::new(manager).await?;
+        let mut app = App::new(manager, config).await?;
         
         // Auto-login if account pubkey is provided
         if let Some(pubkey) = &account_pubkey {
             app.auto_login_by_pubkey(pubkey).await?;
         }
+
+        app.relay_profile = relay_profile;
+        if let Err(e) = app.apply_relay_profile().await {
+            // An unknown --relay-profile is a usage error, but a saved choice whose profile was
+            // since removed from the config must not lock out `relay profile use`, which fixes it
+            if app.relay_profile.is_some() {
+                return Err(e);
+            }
+            if !quiet {
+                eprintln!("{} {}", console::style("⚠️ Using the default relay profile:").yellow(), e);
+            }
+        }
         
         Ok(Self {
             app,
@@ -49,18 +77,7 @@ impl CliHandler {
     }
 
     pub async fn handle_command(&mut self, command: Commands) -> Result<()> {
-        let result = match command {
-            Commands::Account { command } => self.handle_account_command(command).await,
-            Commands::Contact { command } => self.handle_contact_command(command).await,
-            Commands::Group { command } => self.handle_group_command(command).await,
-            Commands::Message { command } => self.handle_message_command(command).await,
-            Commands::Relay { command } => self.handle_relay_command(command).await,
-            Commands::Batch { file } => self.handle_batch_command(file).await,
-            Commands::Status => self.handle_status_command().await,
-            Commands::Keys { command } => self.handle_keys_command(command).await,
-        };
-
-        match result {
+        match self.execute(command).await {
             Ok(output) => {
                 if !self.quiet {
                     println!("{}", output);
@@ -68,23 +85,48 @@ impl CliHandler {
                 Ok(())
             }
             Err(e) => {
-                let error_result = CommandResult::<()>::error(e.to_string());
-                let output = self.format_output(&error_result)?;
+                let data = e.downcast_ref::<FailureWithData>().map(|failure| failure.data.clone());
+                let error = CliError::from(e);
+                let output = self.format_output(&CommandResult {
+                    data,
+                    ..CommandResult::failure(&error)
+                })?;
                 if !self.quiet {
                     eprintln!("{}", output);
                 }
-                std::process::exit(1);
+                std::process::exit(error.exit_code());
             }
         }
     }
 
+    /// Run a command and return its formatted output
+    pub async fn execute(&mut self, command: Commands) -> Result<String> {
+        // Commands that publish through the account's relays must stay within --offline/--relays
+        if command.uses_account_relays() {
+            self.app.check_account_relays()?;
+        }
+        match command {
+            Commands::Account { command } => self.handle_account_command(command).await,
+            Commands::Contact { command } => self.handle_contact_command(command).await,
+            Commands::Group { command } => self.handle_group_command(command).await,
+            Commands::Message { command } => self.handle_message_command(command).await,
+            Commands::Invite { command } => self.handle_invite_command(command).await,
+            Commands::Relay { command } => self.handle_relay_command(command).await,
+            Commands::Batch { file } => self.handle_batch_command(file).await,
+            Commands::Status => self.handle_status_command().await,
+            Commands::Keys { command } => self.handle_keys_command(command).await,
+            Commands::History { command } => self.handle_history_command(command).await,
+            Commands::Config { command } => self.handle_config_command(command).await,
+        }
+    }
+
     async fn handle_account_command(&mut self, command: AccountCommands) -> Result<String> {
         match command {
             AccountCommands::Create { name, about } => {
-                let account = self.app.account_manager.create_identity().await?;
+                let mut account = self.app.account_manager.create_identity().await?;
                 
                 // Set up default relays
-                self.app.setup_default_relays(&account).await?;
+                self.app.setup_default_relays(&mut account).await?;
                 
                 // Update metadata if provided
                 if name.is_some() || about.is_some() {
@@ -118,9 +160,33 @@ impl CliHandler {
                 }));
                 self.format_output(&result)
             }
-            AccountCommands::List => {
-                let accounts = self.app.account_manager.fetch_accounts().await?;
+            AccountCommands::List { unread } => {
+                let mut accounts = self.app.account_manager.fetch_accounts().await?;
+                if unread {
+                    for account in self.app.account_manager.logged_in_accounts().await? {
+                        let pubkey = account.pubkey.to_hex();
+                        let markers = self.app.storage.load_read_markers(&pubkey).await?;
+                        let count = self.app.groups.unread_count(&account, &markers).await?;
+                        if let Some(data) = accounts.iter_mut().find(|a| a.pubkey == pubkey) {
+                            data.unread = Some(count);
+                        }
+                    }
+                }
                 let result = CommandResult::success(accounts);
+                self.format_view(View::Accounts, &result)
+            }
+            AccountCommands::Switch { account } => {
+                let account = self.app.account_manager.resolve_account(&account).await?;
+                let account = self.app.account_manager.switch_account(account).await?;
+                let name = self.app.account_manager.fetch_accounts().await?
+                    .into_iter()
+                    .find(|a| a.current)
+                    .and_then(|a| a.name);
+                let result = CommandResult::success(serde_json::json!({
+                    "pubkey": account.pubkey.to_hex(),
+                    "name": name,
+                    "message": "Switched account"
+                }));
                 self.format_output(&result)
             }
             AccountCommands::Info => {
@@ -132,8 +198,7 @@ impl CliHandler {
                     }));
                     self.format_output(&result)
                 } else {
-                    let result = CommandResult::<()>::error("No account logged in".to_string());
-                    self.format_output(&result)
+                    Err(CliError::NotLoggedIn.into())
                 }
             }
             AccountCommands::Export { private } => {
@@ -179,21 +244,17 @@ impl CliHandler {
     async fn handle_contact_command(&mut self, command: ContactCommands) -> Result<String> {
         match command {
             ContactCommands::Add { pubkey, name } => {
+                let identity = identifiers::resolve(&pubkey, &self.app.contacts).await?;
+                let contact_pubkey = identity.pubkey;
+
                 // First add to CLI's ContactManager for local use
-                self.app.contacts.add(name.clone(), pubkey.clone()).await?;
+                let lookup_relays = self.app.relays.lookup_relays();
+                self.app.contacts.add(name.clone(), identity, lookup_relays).await?;
                 // Save contacts to storage after adding
                 self.app.storage.save_contacts(&self.app.contacts).await?;
                 
                 // Also add to WhiteNoise's contact system for group/DM functionality
                 if let Some(account) = self.app.account_manager.get_current_account() {
-                    let contact_pubkey = if pubkey.starts_with("npub") {
-                        whitenoise::PublicKey::parse(&pubkey)
-                            .map_err(|e| anyhow::anyhow!("Invalid npub format: {:?}", e))?
-                    } else {
-                        whitenoise::PublicKey::from_hex(&pubkey)
-                            .map_err(|e| anyhow::anyhow!("Invalid hex format: {:?}", e))?
-                    };
-                    
                     let whitenoise = whitenoise::Whitenoise::get_instance()
                         .map_err(|e| anyhow::anyhow!("Failed to get WhiteNoise instance: {:?}", e))?;
                     
@@ -202,13 +263,14 @@ impl CliHandler {
                 }
                 
                 let result = CommandResult::success(serde_json::json!({
-                    "pubkey": pubkey,
+                    "pubkey": contact_pubkey.to_hex(),
                     "name": name,
                     "message": "Contact added successfully"
                 }));
                 self.format_output(&result)
             }
             ContactCommands::Remove { pubkey } => {
+                let pubkey = self.resolve_pubkey(&pubkey).await?.to_hex();
                 self.app.contacts.remove(&pubkey).await?;
                 // Save contacts to storage after removing
                 self.app.storage.save_contacts(&self.app.contacts).await?;
@@ -221,7 +283,7 @@ impl CliHandler {
             ContactCommands::List => {
                 let contacts = self.app.contacts.list();
                 let result = CommandResult::success(contacts);
-                self.format_output(&result)
+                self.format_view(View::Contacts, &result)
             }
             ContactCommands::Fetch => {
                 if let Some(account) = self.app.account_manager.get_current_account() {
@@ -233,17 +295,16 @@ impl CliHandler {
                     }));
                     self.format_output(&result)
                 } else {
-                    let result = CommandResult::<()>::error("No account logged in".to_string());
-                    self.format_output(&result)
+                    Err(CliError::NotLoggedIn.into())
                 }
             }
             ContactCommands::Show { pubkey } => {
+                let pubkey = self.resolve_pubkey(&pubkey).await?.to_hex();
                 if let Some(contact) = self.app.contacts.list().iter().find(|c| c.public_key == pubkey) {
                     let result = CommandResult::success(contact);
-                    self.format_output(&result)
+                    self.format_view(View::Contacts, &result)
                 } else {
-                    let result = CommandResult::<()>::error("Contact not found".to_string());
-                    self.format_output(&result)
+                    Err(CliError::NotFound(format!("Contact not found: {}", pubkey)).into())
                 }
             }
         }
@@ -254,11 +315,7 @@ impl CliHandler {
             GroupCommands::Create { name, description, members } => {
                 if let Some(account) = self.app.account_manager.get_current_account() {
                     let member_pubkeys = if let Some(members_str) = members {
-                        let keys: Result<Vec<PublicKey>, _> = members_str
-                            .split(',')
-                            .map(|s| PublicKey::from_hex(s.trim()).or_else(|_| PublicKey::parse(s.trim())))
-                            .collect();
-                        keys?
+                        self.resolve_pubkey_list(&members_str).await?
                     } else {
                         // Empty member list - creator is automatically added by MLS protocol
                         vec![]
@@ -282,38 +339,225 @@ impl CliHandler {
                     }));
                     self.format_output(&result)
                 } else {
-                    let result = CommandResult::<()>::error("No account logged in".to_string());
-                    self.format_output(&result)
+                    Err(CliError::NotLoggedIn.into())
                 }
             }
-            GroupCommands::List => {
+            GroupCommands::List { state } => {
                 if let Some(account) = self.app.account_manager.get_current_account() {
-                    let groups = self.app.groups.fetch_groups(account).await?;
+                    let archived = self.app.storage.load_archived_groups(&account.pubkey.to_hex()).await?;
+                    let groups = self.app.groups.list_groups(account, &archived, state).await?;
                     let result = CommandResult::success(groups);
-                    self.format_output(&result)
+                    self.format_view(View::Groups, &result)
+                } else {
+                    Err(CliError::NotLoggedIn.into())
+                }
+            }
+            GroupCommands::Export { group_id, format, file } => {
+                let account = if let Some(account) = self.app.account_manager.get_current_account() {
+                    account.clone()
                 } else {
-                    let result = CommandResult::<()>::error("No account logged in".to_string());
+                    return Err(CliError::NotLoggedIn.into());
+                };
+
+                let group = self.app.groups.fetch_groups(&account).await?
+                    .into_iter()
+                    .find(|g| g.mls_group_id == group_id)
+                    .ok_or_else(|| CliError::NotFound(format!("Group not found: {}", group_id)))?;
+                let path = PathBuf::from(file.unwrap_or_else(|| format!("{}.{}", slugify(&group.name), format.extension())));
+                if path.exists() {
+                    return Err(CliError::InvalidInput(format!("{} already exists", path.display())).into());
+                }
+
+                let group_id_obj = crate::groups::GroupManager::group_id_from_string(&group_id)?;
+                let messages = self.app.groups.fetch_aggregated_messages_for_group(&account, &group_id_obj).await?;
+                let names: HashMap<String, String> = self.app.contacts.list().iter()
+                    .map(|c| (c.public_key.clone(), c.name.clone()))
+                    .collect();
+                let mut archive = ConversationArchive::new(&group, &messages, &names, &account.pubkey.to_hex());
+
+                let content = match format {
+                    ExportFormat::Json => {
+                        let private_key = self.app.account_manager.export_nsec().await?;
+                        archive.sign(&private_key)?;
+                        serde_json::to_string_pretty(&archive)?
+                    }
+                    ExportFormat::Markdown => archive.to_markdown(),
+                    ExportFormat::Html => archive.to_html(),
+                };
+                std::fs::write(&path, content)?;
+
+                let result = CommandResult::success(serde_json::json!({
+                    "group_id": group_id,
+                    "format": format.extension(),
+                    "file": path,
+                    "messages": archive.messages.len(),
+                    "signed": archive.signature.is_some()
+                }));
+                self.format_output(&result)
+            }
+            GroupCommands::Leave { group_id } => {
+                if let Some(account) = self.app.account_manager.get_current_account() {
+                    let group_id_obj = crate::groups::GroupManager::group_id_from_string(&group_id)?;
+                    self.app.groups.leave_group(account, &group_id_obj).await?;
+
+                    let result = CommandResult::success(serde_json::json!({
+                        "group_id": group_id,
+                        "message": "Left group"
+                    }));
                     self.format_output(&result)
+                } else {
+                    Err(CliError::NotLoggedIn.into())
                 }
             }
+            GroupCommands::Archive { group_id } => self.set_group_archived(group_id, true).await,
+            GroupCommands::Unarchive { group_id } => self.set_group_archived(group_id, false).await,
             GroupCommands::Show { group_id } => {
                 if let Some(account) = self.app.account_manager.get_current_account() {
                     let groups = self.app.groups.fetch_groups(account).await?;
                     if let Some(group) = groups.iter().find(|g| g.mls_group_id == group_id) {
                         let result = CommandResult::success(group);
-                        self.format_output(&result)
+                        self.format_view(View::Groups, &result)
                     } else {
-                        let result = CommandResult::<()>::error("Group not found".to_string());
-                        self.format_output(&result)
+                        Err(CliError::NotFound(format!("Group not found: {}", group_id)).into())
                     }
                 } else {
-                    let result = CommandResult::<()>::error("No account logged in".to_string());
-                    self.format_output(&result)
+                    Err(CliError::NotLoggedIn.into())
                 }
             }
-            GroupCommands::Join { group_id: _ } => {
-                let result = CommandResult::<()>::error("Join command requires interactive mode".to_string());
-                self.format_output(&result)
+            GroupCommands::Update { group_id, name, description, image, clear_image, relays } => {
+                if let Some(account) = self.app.account_manager.get_current_account() {
+                    let group_id_obj = crate::groups::GroupManager::group_id_from_string(&group_id)?;
+                    if let Some(image) = &image {
+                        url::Url::parse(image)
+                            .map_err(|e| CliError::InvalidInput(format!("Invalid image URL {}: {}", image, e)))?;
+                    }
+                    let relays = relays
+                        .map(|list| {
+                            list.split(',')
+                                .map(|relay| crate::relays::RelayManager::validate_relay_url(relay.trim()))
+                                .collect::<Result<Vec<_>>>()
+                        })
+                        .transpose()?;
+
+                    let update = NostrGroupDataUpdate {
+                        name,
+                        description,
+                        image_url: if clear_image { Some(None) } else { image.clone().map(Some) },
+                        // A plain URL is not encrypted, so drop any key left from the previous image
+                        image_key: (clear_image || image.is_some()).then_some(None),
+                        relays,
+                        ..Default::default()
+                    };
+                    let account = account.clone();
+                    let group = self.app.groups.update_group_metadata(&account, &group_id_obj, update).await?;
+                    let result = CommandResult::success(group);
+                    self.format_view(View::Groups, &result)
+                } else {
+                    Err(CliError::NotLoggedIn.into())
+                }
+            }
+            GroupCommands::Join { group_id, history } => {
+                if let Some(account) = self.app.account_manager.get_current_account() {
+                    let account = account.clone();
+                    let group_id_obj = crate::groups::GroupManager::group_id_from_string(&group_id)?;
+                    self.stream_group(&account, &group_id, &group_id_obj, history).await
+                } else {
+                    Err(CliError::NotLoggedIn.into())
+                }
+            }
+            GroupCommands::AddMember { group_id, members } => {
+                if let Some(account) = self.app.account_manager.get_current_account() {
+                    let group_id_obj = crate::groups::GroupManager::group_id_from_string(&group_id)?;
+                    let member_pubkeys = self.resolve_pubkey_list(&members).await?;
+
+                    self.app.groups.ensure_admin(account, &group_id_obj).await?;
+                    self.app.groups.add_members_to_group(account, &group_id_obj, member_pubkeys.clone()).await?;
+                    let membership = self.app.groups.membership(account, &group_id_obj).await?;
+
+                    let result = CommandResult::success(serde_json::json!({
+                        "group_id": group_id,
+                        "added": member_pubkeys.iter().map(|pk| pk.to_hex()).collect::<Vec<_>>(),
+                        "members": membership.members,
+                        "admins": membership.admins,
+                        "epoch": membership.epoch
+                    }));
+                    self.format_view(View::Members, &result)
+                } else {
+                    Err(CliError::NotLoggedIn.into())
+                }
+            }
+            GroupCommands::RemoveMember { group_id, members } => {
+                if let Some(account) = self.app.account_manager.get_current_account() {
+                    let group_id_obj = crate::groups::GroupManager::group_id_from_string(&group_id)?;
+                    let member_pubkeys = self.resolve_pubkey_list(&members).await?;
+
+                    self.app.groups.ensure_admin(account, &group_id_obj).await?;
+                    self.app.groups.remove_members_from_group(account, &group_id_obj, member_pubkeys.clone()).await?;
+                    let membership = self.app.groups.membership(account, &group_id_obj).await?;
+
+                    let result = CommandResult::success(serde_json::json!({
+                        "group_id": group_id,
+                        "removed": member_pubkeys.iter().map(|pk| pk.to_hex()).collect::<Vec<_>>(),
+                        "members": membership.members,
+                        "admins": membership.admins,
+                        "epoch": membership.epoch
+                    }));
+                    self.format_view(View::Members, &result)
+                } else {
+                    Err(CliError::NotLoggedIn.into())
+                }
+            }
+            GroupCommands::Members { group_id } | GroupCommands::Admins { group_id } => {
+                if let Some(account) = self.app.account_manager.get_current_account() {
+                    let group_id_obj = crate::groups::GroupManager::group_id_from_string(&group_id)?;
+                    let membership = self.app.groups.membership(account, &group_id_obj).await?;
+                    let result = CommandResult::success(membership);
+                    self.format_view(View::Members, &result)
+                } else {
+                    Err(CliError::NotLoggedIn.into())
+                }
+            }
+            GroupCommands::Promote { group_id, member } => {
+                if let Some(account) = self.app.account_manager.get_current_account() {
+                    let group_id_obj = crate::groups::GroupManager::group_id_from_string(&group_id)?;
+                    let member_key = self.resolve_pubkey(&member).await?;
+
+                    let changed = self.app.groups.promote_admin(account, &group_id_obj, member_key).await?;
+                    let membership = self.app.groups.membership(account, &group_id_obj).await?;
+
+                    let result = CommandResult::success(serde_json::json!({
+                        "group_id": group_id,
+                        "member": member_key.to_hex(),
+                        "changed": changed,
+                        "members": membership.members,
+                        "admins": membership.admins,
+                        "epoch": membership.epoch
+                    }));
+                    self.format_view(View::Members, &result)
+                } else {
+                    Err(CliError::NotLoggedIn.into())
+                }
+            }
+            GroupCommands::Demote { group_id, member } => {
+                if let Some(account) = self.app.account_manager.get_current_account() {
+                    let group_id_obj = crate::groups::GroupManager::group_id_from_string(&group_id)?;
+                    let member_key = self.resolve_pubkey(&member).await?;
+
+                    let changed = self.app.groups.demote_admin(account, &group_id_obj, member_key).await?;
+                    let membership = self.app.groups.membership(account, &group_id_obj).await?;
+
+                    let result = CommandResult::success(serde_json::json!({
+                        "group_id": group_id,
+                        "member": member_key.to_hex(),
+                        "changed": changed,
+                        "members": membership.members,
+                        "admins": membership.admins,
+                        "epoch": membership.epoch
+                    }));
+                    self.format_view(View::Members, &result)
+                } else {
+                    Err(CliError::NotLoggedIn.into())
+                }
             }
         }
     }
@@ -323,29 +567,32 @@ impl CliHandler {
             MessageCommands::Send { group_id, message, kind } => {
                 if let Some(account) = self.app.account_manager.get_current_account() {
                     let group_id_obj = crate::groups::GroupManager::group_id_from_string(&group_id)?;
+                    let outgoing = match kind {
+                        Some(kind) => OutgoingMessage::Text { content: message.clone(), kind: MessageKind::for_text(kind)? },
+                        None => OutgoingMessage::text(message.clone()),
+                    };
+                    let kind = outgoing.kind().as_u16();
                     let sent_message = self.app.groups.send_message_to_group(
                         account,
                         &group_id_obj,
-                        message.clone(),
-                        kind,
+                        outgoing,
                     ).await?;
 
                     let result = CommandResult::success(serde_json::json!({
                         "group_id": group_id,
                         "message": message,
                         "message_id": sent_message.message.id.to_hex(),
+                        "kind": kind,
                         "status": "sent"
                     }));
                     self.format_output(&result)
                 } else {
-                    let result = CommandResult::<()>::error("No account logged in".to_string());
-                    self.format_output(&result)
+                    Err(CliError::NotLoggedIn.into())
                 }
             }
             MessageCommands::Dm { recipient, message } => {
                 if let Some(account) = self.app.account_manager.get_current_account() {
-                    let recipient_key = PublicKey::from_hex(&recipient)
-                        .or_else(|_| PublicKey::parse(&recipient))?;
+                    let recipient_key = self.resolve_pubkey(&recipient).await?;
 
                     // Get or create DM group with recipient
                     let dm_group_id = self.app.groups.get_or_create_dm_group(
@@ -357,8 +604,7 @@ impl CliHandler {
                     let sent_message = self.app.groups.send_message_to_group(
                         account,
                         &dm_group_id,
-                        message.clone(),
-                        1, // Text message kind
+                        OutgoingMessage::text(message.clone()),
                     ).await?;
 
                     let result = CommandResult::success(serde_json::json!({
@@ -370,11 +616,10 @@ impl CliHandler {
                     }));
                     self.format_output(&result)
                 } else {
-                    let result = CommandResult::<()>::error("No account logged in".to_string());
-                    self.format_output(&result)
+                    Err(CliError::NotLoggedIn.into())
                 }
             }
-            MessageCommands::List { group_id, limit } => {
+            MessageCommands::List { group_id, page, mark_read } => {
                 if let Some(account) = self.app.account_manager.get_current_account() {
                     let group_id_obj = crate::groups::GroupManager::group_id_from_string(&group_id)?;
                     let messages = self.app.groups.fetch_aggregated_messages_for_group(
@@ -382,22 +627,23 @@ impl CliHandler {
                         &group_id_obj,
                     ).await?;
 
-                    let limited_messages: Vec<_> = messages.iter().rev().take(limit).rev().collect();
+                    let (messages, page_info) = paginate_messages(messages, &page)?;
+                    if let Some(newest) = messages.last().filter(|_| mark_read) {
+                        self.app.storage.mark_group_read(&account.pubkey.to_hex(), &group_id, newest.created_at.as_u64()).await?;
+                    }
                     let result = CommandResult::success(serde_json::json!({
                         "group_id": group_id,
-                        "messages": limited_messages,
-                        "count": limited_messages.len()
-                    }));
-                    self.format_output(&result)
+                        "messages": messages,
+                        "count": messages.len()
+                    })).with_page(page_info);
+                    self.format_view(View::Messages, &result)
                 } else {
-                    let result = CommandResult::<()>::error("No account logged in".to_string());
-                    self.format_output(&result)
+                    Err(CliError::NotLoggedIn.into())
                 }
             }
-            MessageCommands::ListDm { contact, limit } => {
+            MessageCommands::ListDm { contact, page, mark_read } => {
                 if let Some(account) = self.app.account_manager.get_current_account() {
-                    let contact_key = PublicKey::from_hex(&contact)
-                        .or_else(|_| PublicKey::parse(&contact))?;
+                    let contact_key = self.resolve_pubkey(&contact).await?;
 
                     // Get DM group with contact
                     if let Some(dm_group_id) = self.app.groups.find_dm_group(
@@ -410,14 +656,18 @@ impl CliHandler {
                             &dm_group_id,
                         ).await?;
 
-                        let limited_messages: Vec<_> = messages.iter().rev().take(limit).rev().collect();
+                        let (messages, page_info) = paginate_messages(messages, &page)?;
+                        if let Some(newest) = messages.last().filter(|_| mark_read) {
+                            let dm_group = crate::groups::GroupManager::group_id_to_string(&dm_group_id);
+                            self.app.storage.mark_group_read(&account.pubkey.to_hex(), &dm_group, newest.created_at.as_u64()).await?;
+                        }
                         let result = CommandResult::success(serde_json::json!({
                             "contact": contact,
                             "dm_group_id": format!("{:?}", dm_group_id),
-                            "messages": limited_messages,
-                            "count": limited_messages.len()
-                        }));
-                        self.format_output(&result)
+                            "messages": messages,
+                            "count": messages.len()
+                        })).with_page(page_info);
+                        self.format_view(View::Messages, &result)
                     } else {
                         let result = CommandResult::success(serde_json::json!({
                             "contact": contact,
@@ -425,17 +675,15 @@ impl CliHandler {
                             "count": 0,
                             "note": "No DM group found with this contact"
                         }));
-                        self.format_output(&result)
+                        self.format_view(View::Messages, &result)
                     }
                 } else {
-                    let result = CommandResult::<()>::error("No account logged in".to_string());
-                    self.format_output(&result)
+                    Err(CliError::NotLoggedIn.into())
                 }
             }
             MessageCommands::GetDmGroup { contact } => {
                 if let Some(account) = self.app.account_manager.get_current_account() {
-                    let contact_key = PublicKey::from_hex(&contact)
-                        .or_else(|_| PublicKey::parse(&contact))?;
+                    let contact_key = self.resolve_pubkey(&contact).await?;
 
                     // Get or create DM group with contact
                     let dm_group_id = self.app.groups.get_or_create_dm_group(
@@ -450,10 +698,303 @@ impl CliHandler {
                     }));
                     self.format_output(&result)
                 } else {
-                    let result = CommandResult::<()>::error("No account logged in".to_string());
+                    Err(CliError::NotLoggedIn.into())
+                }
+            }
+            MessageCommands::Reply { group_id, message_id, message } => {
+                if let Some(account) = self.app.account_manager.get_current_account() {
+                    let group_id_obj = crate::groups::GroupManager::group_id_from_string(&group_id)?;
+                    let target = self.app.groups.find_message(account, &group_id_obj, &message_id).await?;
+                    let sent_message = self.app.groups.send_message_to_group(
+                        account,
+                        &group_id_obj,
+                        OutgoingMessage::reply(&target, message.clone()),
+                    ).await?;
+
+                    let result = CommandResult::success(serde_json::json!({
+                        "group_id": group_id,
+                        "message": message,
+                        "message_id": sent_message.message.id.to_hex(),
+                        "reply_to": target.id,
+                        "status": "sent"
+                    }));
+                    self.format_output(&result)
+                } else {
+                    Err(CliError::NotLoggedIn.into())
+                }
+            }
+            MessageCommands::React { group_id, message_id, emoji } => {
+                if let Some(account) = self.app.account_manager.get_current_account() {
+                    let group_id_obj = crate::groups::GroupManager::group_id_from_string(&group_id)?;
+                    let target = self.app.groups.find_message(account, &group_id_obj, &message_id).await?;
+                    let sent_message = self.app.groups.send_message_to_group(
+                        account,
+                        &group_id_obj,
+                        OutgoingMessage::reaction(&target, emoji.clone()),
+                    ).await?;
+
+                    let result = CommandResult::success(serde_json::json!({
+                        "group_id": group_id,
+                        "emoji": emoji,
+                        "message_id": sent_message.message.id.to_hex(),
+                        "reacted_to": target.id,
+                        "status": "sent"
+                    }));
+                    self.format_output(&result)
+                } else {
+                    Err(CliError::NotLoggedIn.into())
+                }
+            }
+            MessageCommands::Delete { group_id, message_id, reason } => {
+                if let Some(account) = self.app.account_manager.get_current_account() {
+                    let group_id_obj = crate::groups::GroupManager::group_id_from_string(&group_id)?;
+                    let target = self.app.groups.find_message(account, &group_id_obj, &message_id).await?;
+                    let sent_message = self.app.groups.send_message_to_group(
+                        account,
+                        &group_id_obj,
+                        OutgoingMessage::deletion(&target, reason),
+                    ).await?;
+
+                    let result = CommandResult::success(serde_json::json!({
+                        "group_id": group_id,
+                        "message_id": sent_message.message.id.to_hex(),
+                        "deleted": target.id,
+                        "status": "sent"
+                    }));
+                    self.format_output(&result)
+                } else {
+                    Err(CliError::NotLoggedIn.into())
+                }
+            }
+            MessageCommands::SendFile { group_id, file, caption, server } => {
+                if let Some(account) = self.app.account_manager.get_current_account() {
+                    let group_id_obj = crate::groups::GroupManager::group_id_from_string(&group_id)?;
+                    let server = server.unwrap_or_else(|| self.app.config.media_server());
+                    let media = MediaManager::new(&server)?;
+                    let secret = self.app.groups.media_secret(account, &group_id_obj, None).await?;
+                    let signer = nostr::Keys::parse(&self.app.account_manager.export_nsec().await?)
+                        .map_err(|e| CliError::InvalidKey(format!("Failed to load the account key: {}", e)))?;
+                    let attachment = media.upload_file(&secret, &signer, Path::new(&file)).await?;
+
+                    let sent_message = self.app.groups.send_message_to_group(
+                        account,
+                        &group_id_obj,
+                        OutgoingMessage::media(attachment.clone(), caption.unwrap_or_default()),
+                    ).await?;
+
+                    let result = CommandResult::success(serde_json::json!({
+                        "group_id": group_id,
+                        "message_id": sent_message.message.id.to_hex(),
+                        "attachment": attachment,
+                        "status": "sent"
+                    }));
                     self.format_output(&result)
+                } else {
+                    Err(CliError::NotLoggedIn.into())
                 }
             }
+            MessageCommands::Download { group_id, message_id, output_dir } => {
+                if let Some(account) = self.app.account_manager.get_current_account() {
+                    let group_id_obj = crate::groups::GroupManager::group_id_from_string(&group_id)?;
+                    let message = self.app.groups.find_message(account, &group_id_obj, &message_id).await?;
+                    let attachments = MediaAttachment::from_tags(&message.tags);
+                    if attachments.is_empty() {
+                        return Err(CliError::NotFound(format!("Message {} has no attachments", message.id)).into());
+                    }
+
+                    let output_dir = PathBuf::from(output_dir.unwrap_or_else(|| ".".to_string()));
+                    let media = MediaManager::new(&self.app.config.media_server())?;
+                    let mut saved = Vec::new();
+                    for attachment in &attachments {
+                        // Never trust a sender-supplied name as a path
+                        let name = Path::new(&attachment.name)
+                            .file_name()
+                            .map(|n| n.to_string_lossy().to_string())
+                            .unwrap_or_else(|| attachment.sha256.clone());
+                        let path = output_dir.join(name);
+                        if path.exists() {
+                            return Err(CliError::InvalidInput(format!("{} already exists", path.display())).into());
+                        }
+
+                        let secret = self.app.groups.media_secret(account, &group_id_obj, Some(attachment.epoch)).await?;
+                        let data = media.download(&secret, attachment).await?;
+                        std::fs::write(&path, &data)?;
+                        saved.push(serde_json::json!({
+                            "path": path,
+                            "mime_type": attachment.mime_type,
+                            "size": data.len()
+                        }));
+                    }
+
+                    let result = CommandResult::success(serde_json::json!({
+                        "group_id": group_id,
+                        "message_id": message.id,
+                        "files": saved
+                    }));
+                    self.format_output(&result)
+                } else {
+                    Err(CliError::NotLoggedIn.into())
+                }
+            }
+            MessageCommands::Search { query, regex, group_id, author, kind, since, until, limit, no_sync } => {
+                let account = if let Some(account) = self.app.account_manager.get_current_account() {
+                    account.clone()
+                } else {
+                    return Err(CliError::NotLoggedIn.into());
+                };
+
+                let author = match author {
+                    Some(author) => Some(self.resolve_pubkey(&author).await?.to_hex()),
+                    None => None,
+                };
+                let search = SearchQuery {
+                    text: query.clone(),
+                    regex,
+                    author,
+                    group_id,
+                    kind,
+                    since: since.map(|s| parse_time_bound(&s, false)).transpose()?,
+                    until: until.map(|u| parse_time_bound(&u, true)).transpose()?,
+                    limit,
+                };
+
+                let pubkey = account.pubkey.to_hex();
+                let groups = self.app.groups.fetch_groups(&account).await?;
+                let mut index = self.app.storage.load_search_index(&pubkey).await?;
+                if !no_sync {
+                    let mut changed = false;
+                    for group in &groups {
+                        if !index.needs_sync(group) {
+                            continue;
+                        }
+                        let group_id_obj = crate::groups::GroupManager::group_id_from_string(&group.mls_group_id)?;
+                        let messages = self.app.groups.fetch_aggregated_messages_for_group(&account, &group_id_obj).await?;
+                        index.sync_group(group, &messages);
+                        changed = true;
+                    }
+                    if changed {
+                        self.app.storage.save_search_index(&pubkey, &index).await?;
+                    }
+                }
+
+                let group_names: HashMap<&str, &str> = groups.iter()
+                    .map(|g| (g.mls_group_id.as_str(), g.name.as_str()))
+                    .collect();
+                let hits: Vec<SearchHit> = index.search(&search)?
+                    .into_iter()
+                    .map(|message| SearchHit {
+                        group_name: group_names.get(message.group_id.as_str()).unwrap_or(&"").to_string(),
+                        message: message.clone(),
+                    })
+                    .collect();
+
+                let result = CommandResult::success(serde_json::json!({
+                    "query": query,
+                    "messages": hits,
+                    "count": hits.len(),
+                    "indexed": index.len()
+                }));
+                self.format_view(View::Messages, &result)
+            }
+            MessageCommands::Watch { group_id, sender, since, accounts, all_accounts } => {
+                let accounts = if all_accounts {
+                    self.app.account_manager.logged_in_accounts().await?
+                } else if let Some(accounts) = accounts {
+                    let mut resolved = Vec::new();
+                    for query in accounts.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+                        resolved.push(self.app.account_manager.resolve_account(query).await?);
+                    }
+                    resolved
+                } else if let Some(account) = self.app.account_manager.get_current_account() {
+                    vec![account.clone()]
+                } else {
+                    return Err(CliError::NotLoggedIn.into());
+                };
+                if accounts.is_empty() {
+                    return Err(CliError::NotLoggedIn.into());
+                }
+
+                let group_filter = group_id
+                    .map(|id| crate::groups::GroupManager::group_id_from_string(&id))
+                    .transpose()?;
+                let sender_filter = match sender {
+                    Some(sender) => Some(self.resolve_pubkey(&sender).await?.to_hex()),
+                    None => None,
+                };
+                self.watch_messages(accounts, group_filter, sender_filter, since).await
+            }
+        }
+    }
+
+    async fn set_group_archived(&mut self, group_id: String, archive: bool) -> Result<String> {
+        let account = if let Some(account) = self.app.account_manager.get_current_account() {
+            account.clone()
+        } else {
+            return Err(CliError::NotLoggedIn.into());
+        };
+
+        let groups = self.app.groups.fetch_groups(&account).await?;
+        if !groups.iter().any(|g| g.mls_group_id == group_id) {
+            return Err(CliError::NotFound(format!("Group not found: {}", group_id)).into());
+        }
+
+        let pubkey = account.pubkey.to_hex();
+        let mut archived = self.app.storage.load_archived_groups(&pubkey).await?;
+        let changed = if archive {
+            archived.insert(group_id.clone())
+        } else {
+            archived.remove(&group_id)
+        };
+        self.app.storage.save_archived_groups(&pubkey, &archived).await?;
+
+        let result = CommandResult::success(serde_json::json!({
+            "group_id": group_id,
+            "archived": archive,
+            "changed": changed
+        }));
+        self.format_output(&result)
+    }
+
+    async fn handle_invite_command(&mut self, command: InviteCommands) -> Result<String> {
+        let account = if let Some(account) = self.app.account_manager.get_current_account() {
+            account.clone()
+        } else {
+            return Err(CliError::NotLoggedIn.into());
+        };
+
+        match command {
+            InviteCommands::List { all } => {
+                let invites = self.app.groups.fetch_invites(&account, all).await?;
+                let result = CommandResult::success(serde_json::json!({
+                    "invites": invites,
+                    "count": invites.len()
+                }));
+                self.format_view(View::Invites, &result)
+            }
+            InviteCommands::Accept { invite_id } => {
+                let invite = self.app.groups.find_pending_invite(&account, &invite_id).await?;
+                self.app.groups.accept_invite(&account, &invite).await?;
+
+                let result = CommandResult::success(serde_json::json!({
+                    "invite_id": invite.id,
+                    "group_id": invite.group_id,
+                    "group_name": invite.group_name,
+                    "status": "accepted"
+                }));
+                self.format_output(&result)
+            }
+            InviteCommands::Decline { invite_id } => {
+                let invite = self.app.groups.find_pending_invite(&account, &invite_id).await?;
+                self.app.groups.decline_invite(&account, &invite).await?;
+
+                let result = CommandResult::success(serde_json::json!({
+                    "invite_id": invite.id,
+                    "group_id": invite.group_id,
+                    "group_name": invite.group_name,
+                    "status": "declined"
+                }));
+                self.format_output(&result)
+            }
         }
     }
 
@@ -474,63 +1015,80 @@ impl CliHandler {
                     }
 
                     let result = CommandResult::success(relay_info);
-                    self.format_output(&result)
+                    self.format_view(View::Relays, &result)
                 } else {
-                    let result = CommandResult::<()>::error("No account logged in".to_string());
-                    self.format_output(&result)
+                    Err(CliError::NotLoggedIn.into())
                 }
             }
             RelayCommands::Add { url, relay_type } => {
                 if let Some(account) = self.app.account_manager.get_current_account() {
+                    let mut account = account.clone();
                     let rt = self.parse_relay_type(&relay_type)?;
-                    self.app.relays.add_relay_to_type(account, rt, url.clone()).await?;
+                    let added = self.app.relays.add_relay_to_type(&mut account, rt, url.clone()).await?;
+                    let relays = crate::relays::RelayManager::account_relays(&account, &rt);
+                    self.app.account_manager.set_current_account(account);
 
                     let result = CommandResult::success(serde_json::json!({
                         "url": url,
                         "relay_type": relay_type,
-                        "message": "Relay added successfully"
+                        "changed": added,
+                        "relays": relays,
+                        "published_kind": added.then(|| crate::relays::RelayManager::relay_list_kind(&rt)),
+                        "message": if added { "Relay added successfully" } else { "Relay already configured" }
                     }));
                     self.format_output(&result)
                 } else {
-                    let result = CommandResult::<()>::error("No account logged in".to_string());
-                    self.format_output(&result)
+                    Err(CliError::NotLoggedIn.into())
                 }
             }
             RelayCommands::Remove { url, relay_type } => {
                 if let Some(account) = self.app.account_manager.get_current_account() {
+                    let mut account = account.clone();
                     let rt = self.parse_relay_type(&relay_type)?;
-                    self.app.relays.remove_relay_from_type(account, rt, &url).await?;
+                    let removed = self.app.relays.remove_relay_from_type(&mut account, rt, &url).await?;
+                    let relays = crate::relays::RelayManager::account_relays(&account, &rt);
+                    self.app.account_manager.set_current_account(account);
 
                     let result = CommandResult::success(serde_json::json!({
                         "url": url,
                         "relay_type": relay_type,
-                        "message": "Relay removed successfully"
+                        "changed": removed,
+                        "relays": relays,
+                        "published_kind": removed.then(|| crate::relays::RelayManager::relay_list_kind(&rt)),
+                        "message": if removed { "Relay removed successfully" } else { "Relay was not configured" }
                     }));
                     self.format_output(&result)
                 } else {
-                    let result = CommandResult::<()>::error("No account logged in".to_string());
-                    self.format_output(&result)
+                    Err(CliError::NotLoggedIn.into())
                 }
             }
+            RelayCommands::Profile { command } => self.handle_relay_profile_command(command).await,
             RelayCommands::Test { url } => {
-                let is_valid = self.app.relays.test_relay_connection(&url).await?;
-                let result = CommandResult::success(serde_json::json!({
-                    "url": url,
-                    "valid": is_valid,
-                    "status": if is_valid { "reachable" } else { "unreachable" }
-                }));
-                self.format_output(&result)
+                let report = self.app.relays.test_relay_connection(&url).await?;
+                let data = serde_json::json!({
+                    "url": report.url,
+                    "status": if report.reachable { "reachable" } else { "unreachable" },
+                    "connect_ms": report.connect_ms,
+                    "latency_ms": report.latency_ms,
+                    "info": report.info,
+                    "info_error": report.info_error,
+                    "failure": report.failure
+                });
+                if !report.reachable {
+                    let reason = report.failure.as_ref().map_or("no response".to_string(), |f| f.message.clone());
+                    return Err(FailureWithData {
+                        error: CliError::Relay(format!("{} is unreachable: {}", report.url, reason)),
+                        data,
+                    }.into());
+                }
+                self.format_output(&CommandResult::success(data))
             }
         }
     }
 
     async fn handle_batch_command(&mut self, file_path: String) -> Result<String> {
         let content = std::fs::read_to_string(&file_path)?;
-        let batch: BatchOperation = if file_path.ends_with(".json") {
-            serde_json::from_str(&content)?
-        } else {
-            return Err(anyhow::anyhow!("Only JSON batch files are supported currently"));
-        };
+        let batch = BatchOperation::parse(&content, std::path::Path::new(&file_path))?;
 
         let mut results = Vec::new();
         for operation in batch.operations {
@@ -567,30 +1125,38 @@ impl CliHandler {
         let helper = KeyringHelper::new()?;
         
         match command {
-            KeysCommands::Store { pubkey, privkey } => {
-                // Validate pubkey
-                let _ = PublicKey::from_hex(&pubkey)
-                    .map_err(|e| anyhow::anyhow!("Invalid public key hex: {}", e))?;
+            KeysCommands::Store { pubkey, privkey, passphrase_stdin } => {
+                // Keys are stored under the hex form of the public key
+                let pubkey = self.resolve_pubkey(&pubkey).await?.to_hex();
+                
+                let migrated = helper.is_legacy()?;
+                let passphrase = read_passphrase(passphrase_stdin, true)?;
                 
                 // Store the key
-                helper.store_key(&pubkey, &privkey)?;
+                helper.store_key(&pubkey, &privkey, &passphrase)?;
                 
                 let result = CommandResult::success(serde_json::json!({
                     "pubkey": pubkey,
+                    "migrated": migrated,
                     "message": "Private key stored successfully"
                 }));
                 self.format_output(&result)
             }
-            KeysCommands::Get { pubkey } => {
-                if let Some(privkey) = helper.get_key(&pubkey)? {
+            KeysCommands::Get { pubkey, passphrase_stdin } => {
+                let pubkey = self.resolve_pubkey(&pubkey).await?.to_hex();
+                // Reading a legacy store migrates it, so the passphrase it gets sealed with must be confirmed
+                let migrated = helper.is_legacy()?;
+                let passphrase = read_passphrase(passphrase_stdin, migrated)?;
+                
+                if let Some(privkey) = helper.get_key(&pubkey, &passphrase)? {
                     let result = CommandResult::success(serde_json::json!({
                         "pubkey": pubkey,
-                        "privkey": privkey
+                        "privkey": privkey,
+                        "migrated": migrated
                     }));
                     self.format_output(&result)
                 } else {
-                    let result = CommandResult::<()>::error(format!("No key found for pubkey: {}", pubkey));
-                    self.format_output(&result)
+                    Err(CliError::NotFound(format!("No key found for pubkey: {}", pubkey)).into())
                 }
             }
             KeysCommands::List => {
@@ -602,6 +1168,7 @@ impl CliHandler {
                 self.format_output(&result)
             }
             KeysCommands::Remove { pubkey } => {
+                let pubkey = self.resolve_pubkey(&pubkey).await?.to_hex();
                 helper.remove_key(&pubkey)?;
                 let result = CommandResult::success(serde_json::json!({
                     "pubkey": pubkey,
@@ -612,6 +1179,131 @@ impl CliHandler {
         }
     }
 
+    async fn handle_history_command(&mut self, command: HistoryCommands) -> Result<String> {
+        match command {
+            HistoryCommands::Import { file } => {
+                let content = std::fs::read_to_string(&file)
+                    .map_err(|e| CliError::InvalidInput(format!("Failed to read {}: {}", file, e)))?;
+                let archive: ConversationArchive = serde_json::from_str(&content)
+                    .map_err(|e| CliError::InvalidInput(format!("Not a conversation archive: {}", e)))?;
+                archive.verify()?;
+
+                let archive_id = self.app.storage.save_imported_archive(&archive).await?;
+                let result = CommandResult::success(serde_json::json!({
+                    "archive_id": archive_id,
+                    "group_name": archive.group.name,
+                    "exported_by": archive.exported_by,
+                    "messages": archive.messages.len(),
+                    "verified": true
+                }));
+                self.format_output(&result)
+            }
+            HistoryCommands::List => {
+                let archives = self.app.storage.load_imported_archives().await?;
+                let summaries: Vec<_> = archives.iter()
+                    .map(|archive| serde_json::json!({
+                        "archive_id": archive.id(),
+                        "group_name": archive.group.name,
+                        "exported_by": archive.exported_by,
+                        "exported_at": archive.exported_at,
+                        "messages": archive.messages.len()
+                    }))
+                    .collect();
+                let result = CommandResult::success(summaries);
+                self.format_output(&result)
+            }
+            HistoryCommands::Show { archive_id, limit } => {
+                let archive = self.app.storage.load_imported_archives().await?
+                    .into_iter()
+                    .find(|archive| archive.id() == archive_id)
+                    .ok_or_else(|| CliError::NotFound(format!("Archive not found: {}", archive_id)))?;
+
+                let skip = limit.map_or(0, |limit| archive.messages.len().saturating_sub(limit));
+                let messages: Vec<_> = archive.messages.iter().skip(skip).collect();
+                let result = CommandResult::success(serde_json::json!({
+                    "archive_id": archive_id,
+                    "group_name": archive.group.name,
+                    "messages": messages,
+                    "count": messages.len()
+                }));
+                self.format_view(View::Messages, &result)
+            }
+        }
+    }
+
+    async fn handle_relay_profile_command(&mut self, command: RelayProfileCommands) -> Result<String> {
+        match command {
+            RelayProfileCommands::List => {
+                let active = self.app.relays.get_config().profile.clone();
+                let profiles: Vec<_> = self.app.config.relay_profile_names().into_iter()
+                    .map(|name| serde_json::json!({ "active": name == active, "name": name }))
+                    .collect();
+                let result = CommandResult::success(serde_json::json!({
+                    "active": active,
+                    "profiles": profiles
+                }));
+                self.format_output(&result)
+            }
+            RelayProfileCommands::Show { name } => {
+                let profile = match name {
+                    Some(name) => self.app.config.relay_profile(&name)?,
+                    None => self.app.relays.get_config().clone(),
+                };
+                let result = CommandResult::success(profile);
+                self.format_output(&result)
+            }
+            RelayProfileCommands::Use { name, publish } => {
+                let mut account = if let Some(account) = self.app.account_manager.get_current_account() {
+                    account.clone()
+                } else {
+                    return Err(CliError::NotLoggedIn.into());
+                };
+
+                let profile = self.app.config.relay_profile(&name)?;
+                if publish {
+                    for relay_type in crate::relays::RelayManager::all_relay_types() {
+                        let relays = profile.relays_for_type(&relay_type).clone();
+                        self.app.relays.update_relays(&mut account, relay_type, relays).await?;
+                    }
+                    self.app.account_manager.set_current_account(account.clone());
+                }
+                // Only remember the choice once every relay list is published
+                self.app.storage.save_account_relay_profile(&account.pubkey.to_hex(), Some(&name)).await?;
+                // Re-resolve rather than set directly so --offline keeps precedence
+                self.app.apply_relay_profile().await?;
+
+                let result = CommandResult::success(serde_json::json!({
+                    "profile": profile,
+                    "published": publish,
+                    "message": format!("Relay profile set to {}", name)
+                }));
+                self.format_output(&result)
+            }
+        }
+    }
+
+    async fn handle_config_command(&mut self, command: ConfigCommands) -> Result<String> {
+        match command {
+            ConfigCommands::Show => {
+                let config = &self.app.config;
+                let result = CommandResult::success(serde_json::json!({
+                    "source": config.source,
+                    "data_dir": config.data_dir(),
+                    "logs_dir": config.logs_dir(),
+                    "state_dir": config.state_dir()?,
+                    "log_filter": config.log_filter,
+                    "output": config.output,
+                    "default_account": config.default_account,
+                    "media_server": config.media_server(),
+                    "relay_profile": self.app.relays.get_config().profile,
+                    "relay_profiles": config.relay_profile_names(),
+                    "relays": self.app.relays.get_config()
+                }));
+                self.format_output(&result)
+            }
+        }
+    }
+
     async fn execute_batch_operation(&mut self, operation: BatchCommand) -> serde_json::Value {
         let result = match operation {
             BatchCommand::AccountCreate { name, about } => {
@@ -628,7 +1320,7 @@ impl CliHandler {
                 self.handle_message_command(MessageCommands::Send { 
                     group_id, 
                     message, 
-                    kind: kind.unwrap_or(1) 
+                    kind
                 }).await
             }
             BatchCommand::MessageDm { recipient, message } => {
@@ -641,8 +1333,24 @@ impl CliHandler {
 
         match result {
             Ok(output) => serde_json::json!({"success": true, "output": output}),
-            Err(e) => serde_json::json!({"success": false, "error": e.to_string()}),
+            Err(e) => {
+                let error = CliError::from(e);
+                serde_json::json!({"success": false, "error": error.to_string(), "error_code": error.code()})
+            }
+        }
+    }
+
+    async fn resolve_pubkey_list(&self, pubkeys: &str) -> Result<Vec<PublicKey>> {
+        let mut resolved = Vec::new();
+        for pubkey in pubkeys.split(',').map(|s| s.trim()).filter(|s| !s.is_empty()) {
+            resolved.push(self.resolve_pubkey(pubkey).await?);
         }
+        Ok(resolved)
+    }
+
+    /// Resolve a hex key, npub, nprofile, NIP-05 identifier or contact name
+    async fn resolve_pubkey(&self, identifier: &str) -> Result<PublicKey> {
+        Ok(identifiers::resolve(identifier, &self.app.contacts).await?.pubkey)
     }
 
     fn parse_relay_type(&self, relay_type: &str) -> Result<RelayType> {
@@ -650,57 +1358,206 @@ impl CliHandler {
             "nostr" => Ok(RelayType::Nostr),
             "inbox" => Ok(RelayType::Inbox),
             "keypackage" | "key_package" => Ok(RelayType::KeyPackage),
-            _ => Err(anyhow::anyhow!("Invalid relay type: {}. Use 'nostr', 'inbox', or 'keypackage'", relay_type)),
+            _ => Err(CliError::InvalidInput(format!("Invalid relay type: {}. Use 'nostr', 'inbox', or 'keypackage'", relay_type)).into()),
         }
     }
 
-    fn format_output<T: serde::Serialize>(&self, result: &CommandResult<T>) -> Result<String> {
-        match self.output_format {
-            OutputFormat::Json => Ok(serde_json::to_string_pretty(result)?),
-            OutputFormat::Yaml => {
-                // For now, output as JSON since YAML support requires additional dependency
-                Ok(serde_json::to_string_pretty(result)?)
-            }
-            OutputFormat::Human => {
-                if result.success {
-                    if let Some(ref data) = result.data {
-                        Ok(serde_json::to_string_pretty(data)?)
-                    } else {
-                        Ok("Operation completed successfully".to_string())
+    /// Print new group messages as NDJSON while sending each stdin line to the
+    /// group. Returns the closing `end` event once stdin closes or on Ctrl-C.
+    async fn stream_group(
+        &mut self,
+        account: &whitenoise::Account,
+        group_id: &str,
+        group_id_obj: &whitenoise::GroupId,
+        history: usize,
+    ) -> Result<String> {
+        use crate::groups::{FeedEvent, MessageData, MessageFeed, FEED_POLL_INTERVAL};
+        use tokio::io::{AsyncBufReadExt, BufReader};
+
+        let mut feed = MessageFeed::new();
+        let mut received = 0;
+        let mut sent = 0;
+
+        let backlog = feed.poll(&self.app.groups, account, group_id_obj).await?;
+        for message in backlog.iter().skip(backlog.len().saturating_sub(history)) {
+            let event = FeedEvent::Message {
+                account: None,
+                group_id: group_id.to_string(),
+                message: MessageData::from_chat_message(message),
+            };
+            println!("{}", event.to_line()?);
+            received += 1;
+        }
+
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        let mut ticker = tokio::time::interval(FEED_POLL_INTERVAL);
+
+        let reason = loop {
+            tokio::select! {
+                line = lines.next_line() => {
+                    let Some(line) = line? else { break "stdin closed" };
+                    let content = line.trim();
+                    if content.is_empty() {
+                        continue;
+                    }
+
+                    let event = match self.app.groups
+                        .send_message_to_group(account, group_id_obj, OutgoingMessage::text(content))
+                        .await
+                    {
+                        Ok(message) => {
+                            let id = message.message.id.to_hex();
+                            feed.mark_seen(group_id_obj, id.clone());
+                            sent += 1;
+                            FeedEvent::Sent { group_id: group_id.to_string(), id, content: content.to_string() }
+                        }
+                        Err(e) => FeedEvent::Error { message: e.to_string() },
+                    };
+                    println!("{}", event.to_line()?);
+                }
+                _ = ticker.tick() => {
+                    let event_lines = match feed.poll(&self.app.groups, account, group_id_obj).await {
+                        Ok(messages) => {
+                            received += messages.len();
+                            messages.iter()
+                                .map(|message| FeedEvent::Message {
+                                    account: None,
+                                    group_id: group_id.to_string(),
+                                    message: MessageData::from_chat_message(message),
+                                })
+                                .collect()
+                        }
+                        Err(e) => vec![FeedEvent::Error { message: e.to_string() }],
+                    };
+                    for event in event_lines {
+                        println!("{}", event.to_line()?);
+                    }
+                }
+                _ = tokio::signal::ctrl_c() => break "interrupted",
+            }
+        };
+
+        FeedEvent::End { reason: reason.to_string(), received, sent }.to_line()
+    }
+
+    /// Print new messages from all groups (or one group) of every account in
+    /// `accounts` as NDJSON until Ctrl-C; with more than one account, each
+    /// message event names the account that received it. Groups joined while
+    /// watching are picked up on the next poll. With `since`, stored messages
+    /// from that second onwards are replayed first, so a consumer resuming
+    /// from its last `created_at` should dedupe by `id`.
+    async fn watch_messages(
+        &mut self,
+        accounts: Vec<whitenoise::Account>,
+        group_filter: Option<whitenoise::GroupId>,
+        sender_filter: Option<String>,
+        since: Option<u64>,
+    ) -> Result<String> {
+        use crate::groups::{FeedEvent, GroupManager, MessageData, MessageFeed, FEED_POLL_INTERVAL};
+
+        let tag_account = accounts.len() > 1;
+        // Each account keeps its own feed, since two local accounts may share a group.
+        // Alongside it: the groups whose startup history is still to be skipped,
+        // `None` until the account's group list was first read.
+        let mut watchers: Vec<(whitenoise::Account, MessageFeed, Option<HashSet<String>>)> = accounts.into_iter()
+            .map(|account| (account, MessageFeed::new(), None))
+            .collect();
+        let mut received = 0;
+        let mut ticker = tokio::time::interval(FEED_POLL_INTERVAL);
+
+        let reason = loop {
+            tokio::select! {
+                _ = ticker.tick() => {
+                    let mut events = Vec::new();
+                    for (account, feed, baseline) in watchers.iter_mut() {
+                        match feed.poll_groups(&self.app.groups, account, group_filter.as_ref()).await {
+                            Ok(polled) => {
+                                let pending = baseline.get_or_insert_with(|| {
+                                    polled.iter().map(|(group_id, _)| GroupManager::group_id_to_string(group_id)).collect()
+                                });
+                                let account_hex = account.pubkey.to_hex();
+                                for (group_id, messages) in polled {
+                                    let id = GroupManager::group_id_to_string(&group_id);
+                                    match messages {
+                                        // Without a checkpoint, a startup group's first fetched history is
+                                        // skipped; groups that appear later are new, so all their messages count
+                                        Ok(_) if since.is_none() && pending.remove(&id) => {}
+                                        Ok(messages) => {
+                                            events.extend(messages.iter()
+                                                .filter(|m| since.is_none_or(|since| m.created_at.as_u64() >= since))
+                                                .filter(|m| sender_filter.as_ref().is_none_or(|s| m.author.to_hex() == *s))
+                                                .map(|m| FeedEvent::Message {
+                                                    account: tag_account.then(|| account_hex.clone()),
+                                                    group_id: id.clone(),
+                                                    message: MessageData::from_chat_message(m),
+                                                }));
+                                        }
+                                        Err(e) => events.push(FeedEvent::Error { message: e.to_string() }),
+                                    }
+                                }
+                            }
+                            Err(e) => events.push(FeedEvent::Error { message: e.to_string() }),
+                        }
+                    }
+
+                    for event in events {
+                        if matches!(event, FeedEvent::Message { .. }) {
+                            received += 1;
+                        }
+                        println!("{}", event.to_line()?);
                     }
-                } else {
-                    Ok(format!("Error: {}", result.error.as_ref().unwrap_or(&"Unknown error".to_string())))
                 }
+                _ = tokio::signal::ctrl_c() => break "interrupted",
             }
+        };
+
+        FeedEvent::End { reason: reason.to_string(), received, sent: 0 }.to_line()
+    }
+
+    fn format_output<T: serde::Serialize>(&self, result: &CommandResult<T>) -> Result<String> {
+        self.format_view(View::Fields, result)
+    }
+
+    /// Like `format_output`, but with a dedicated renderer for human output
+    fn format_view<T: serde::Serialize>(&self, view: View, result: &CommandResult<T>) -> Result<String> {
+        match self.output_format {
+            OutputFormat::Json => Ok(serde_json::to_string_pretty(result)?),
+            OutputFormat::Yaml => Ok(serde_yaml::to_string(result)?.trim_end().to_string()),
+            OutputFormat::Human => self.human_renderer().render(view, result),
         }
     }
+
+    fn human_renderer(&self) -> HumanRenderer {
+        let names = self.app.contacts.list().iter()
+            .map(|c| (c.public_key.clone(), c.name.clone()))
+            .collect();
+        let own_pubkey = self.app.account_manager.get_current_account().map(|a| a.pubkey.to_hex());
+        // Headers and summaries only help at a terminal; piped output stays plain
+        HumanRenderer::new(names, own_pubkey, !console::user_attended())
+    }
+}
+
+/// File-name friendly version of a group name
+fn slugify(name: &str) -> String {
+    let slug: String = name
+        .chars()
+        .map(|c| if c.is_alphanumeric() { c.to_ascii_lowercase() } else { '-' })
+        .collect();
+    let slug = slug.split('-').filter(|part| !part.is_empty()).collect::<Vec<_>>().join("-");
+    if slug.is_empty() { "conversation".to_string() } else { slug }
 }
 
 // Extension trait to add setup_default_relays method
 trait AppExtensions {
-    async fn setup_default_relays(&mut self, account: &whitenoise::Account) -> Result<()>;
+    async fn setup_default_relays(&mut self, account: &mut whitenoise::Account) -> Result<()>;
 }
 
 impl AppExtensions for App {
-    async fn setup_default_relays(&mut self, account: &whitenoise::Account) -> Result<()> {
+    async fn setup_default_relays(&mut self, account: &mut whitenoise::Account) -> Result<()> {
         use crate::relays::RelayManager;
         
         for relay_type in RelayManager::all_relay_types() {
-            let default_relays = match relay_type {
-                RelayType::Nostr => vec![
-                    "wss://relay.damus.io".to_string(),
-                    "wss://relay.primal.net".to_string(),
-                    "wss://nos.lol".to_string(),
-                ],
-                RelayType::Inbox => vec![
-                    "wss://relay.damus.io".to_string(),
-                    "wss://relay.primal.net".to_string(),
-                ],
-                RelayType::KeyPackage => vec![
-                    "wss://relay.damus.io".to_string(),
-                    "wss://nos.lol".to_string(),
-                ],
-            };
+            let default_relays = self.relays.get_relays_for_type(&relay_type).clone();
             
             if let Err(_) = self.relays.update_relays(account, relay_type, default_relays).await {
                 // Ignore relay setup errors in CLI mode
@@ -717,6 +1574,127 @@ impl AppExtensions for App {
             // Ignore key package publishing errors
         }
 
+        self.account_manager.set_current_account(account.clone());
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{cli::Cli, relays::NetworkPolicy, test_relay::MockRelay};
+    use clap::Parser;
+    use serde_json::Value;
+    use std::path::PathBuf;
+    use std::time::Duration;
+
+    /// Temporary directory removed when the test ends, even on panic
+    struct TempDir(PathBuf);
+
+    impl TempDir {
+        fn new() -> Self {
+            let dir = std::env::temp_dir().join(format!("whitenoise_cli_test_{}", uuid::Uuid::new_v4()));
+            std::fs::create_dir_all(&dir).unwrap();
+            Self(dir)
+        }
+    }
+
+    impl Drop for TempDir {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_dir_all(&self.0);
+        }
+    }
+
+    /// Run a command line through the handler and return the result's `data`
+    async fn run(handler: &mut CliHandler, args: &[&str]) -> Value {
+        let cli = Cli::try_parse_from(std::iter::once("whitenoise-cli").chain(args.iter().copied()))
+            .unwrap_or_else(|e| panic!("{}: {}", args.join(" "), e));
+        let output = handler.execute(cli.command.expect("a command")).await
+            .unwrap_or_else(|e| panic!("{}: {:?}", args.join(" "), e));
+        let result: Value = serde_json::from_str(&output).unwrap();
+        assert_eq!(result["success"], true, "{}", output);
+        result["data"].clone()
+    }
+
+    /// Repeat a command until `done` accepts its data; welcomes and group
+    /// messages arrive from the relay in the background
+    async fn run_until(handler: &mut CliHandler, args: &[&str], done: impl Fn(&Value) -> bool) -> Value {
+        for _ in 0..100 {
+            let data = run(handler, args).await;
+            if done(&data) {
+                return data;
+            }
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+        panic!("Timed out waiting on `{}`", args.join(" "));
+    }
+
+    fn has_message(data: &Value, content: &str) -> bool {
+        data["messages"].as_array().is_some_and(|messages| messages.iter().any(|m| m["content"] == content))
+    }
+
+    /// Switch to `pubkey`, wait for `expected` invitations and accept them
+    async fn accept_invites(handler: &mut CliHandler, pubkey: &str, expected: usize) {
+        run(handler, &["account", "switch", pubkey]).await;
+        let data = run_until(handler, &["invite", "list", "--all"], |data| {
+            data["count"].as_u64().is_some_and(|count| count as usize >= expected)
+        }).await;
+        for invite in data["invites"].as_array().unwrap() {
+            if invite["state"] == "pending" {
+                run(handler, &["invite", "accept", invite["id"].as_str().unwrap()]).await;
+            }
+        }
+    }
+
+    // Three accounts share one WhiteNoise instance and talk through an
+    // in-process relay, the only relay `--relays` allows
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_groups_and_dms_over_mock_relay() {
+        let relay = MockRelay::start().await;
+        NetworkPolicy::from_flags(false, Some(relay.url())).unwrap().install_for_tests();
+
+        let dir = TempDir::new();
+        let config = Config {
+            data_dir: Some(dir.0.join("data")),
+            logs_dir: Some(dir.0.join("logs")),
+            state_dir: Some(dir.0.join("state")),
+            ..Config::default()
+        };
+        let mut handler = CliHandler::new(config, OutputFormat::Json, true, None, None).await.unwrap();
+
+        let mut pubkeys = Vec::new();
+        for name in ["alice", "bob", "carol"] {
+            let data = run(&mut handler, &["account", "create", "--name", name]).await;
+            pubkeys.push(data["pubkey"].as_str().unwrap().to_string());
+        }
+        let [alice, bob, carol] = [&pubkeys[0], &pubkeys[1], &pubkeys[2]];
+        let accounts = run(&mut handler, &["account", "list"]).await;
+        assert_eq!(accounts.as_array().map(Vec::len), Some(3));
+
+        run(&mut handler, &["account", "switch", alice]).await;
+        run(&mut handler, &["message", "dm", "--recipient", bob, "--message", "hi bob"]).await;
+        let members = format!("{},{}", bob, carol);
+        let group = run(&mut handler, &["group", "create", "--name", "Team", "--members", &members]).await;
+        let group_id = group["group_id"].as_str().unwrap().to_string();
+        run(&mut handler, &["message", "send", "--group-id", &group_id, "--message", "hello team"]).await;
+
+        accept_invites(&mut handler, bob, 2).await;
+        run_until(&mut handler, &["message", "list", "--group-id", &group_id], |data| has_message(data, "hello team")).await;
+        run_until(&mut handler, &["message", "list-dm", "--contact", alice], |data| has_message(data, "hi bob")).await;
+        run(&mut handler, &["message", "send", "--group-id", &group_id, "--message", "hi all"]).await;
+
+        accept_invites(&mut handler, carol, 1).await;
+        let data = run_until(&mut handler, &["message", "list", "--group-id", &group_id], |data| {
+            has_message(data, "hello team") && has_message(data, "hi all")
+        }).await;
+        assert_eq!(data["count"], 2);
+
+        // Welcomes travel gift-wrapped and group messages MLS-encrypted
+        assert!(relay.events_of_kind(1059).len() >= 3);
+        assert!(relay.events_of_kind(445).len() >= 3);
+        assert!(relay.events().iter().all(|e| {
+            let content = e["content"].as_str().unwrap_or_default();
+            !content.contains("hello team") && !content.contains("hi bob")
+        }));
+    }
+}
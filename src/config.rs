@@ -0,0 +1,290 @@
+use anyhow::Result;
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use crate::{
+    cli::OutputFormat,
+    errors::CliError,
+    media::DEFAULT_MEDIA_SERVER,
+    relays::{RelayConfig, BUILTIN_RELAY_PROFILES, DEFAULT_RELAY_PROFILE},
+};
+
+/// Environment variable naming a config file when `--config` is not given
+pub const CONFIG_ENV: &str = "WHITENOISE_CLI_CONFIG";
+
+const CONFIG_FILE_NAMES: [&str; 2] = ["config.toml", "config.json"];
+
+/// User configuration loaded from TOML or JSON, with `WHITENOISE_CLI_*`
+/// environment variables taking precedence over file values.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    /// WhiteNoise database directory
+    pub data_dir: Option<PathBuf>,
+    /// WhiteNoise log directory
+    pub logs_dir: Option<PathBuf>,
+    /// CLI state (contacts, read markers, search index); defaults to `./.whitenoise-cli`
+    pub state_dir: Option<PathBuf>,
+    /// Tracing filter in `RUST_LOG` syntax
+    pub log_filter: Option<String>,
+    /// Output format used when `--output` is not given
+    pub output: Option<OutputFormat>,
+    /// Account public key used when `--account` is not given
+    pub default_account: Option<String>,
+    /// Overrides for the relays of the `default` relay profile
+    pub relays: RelayDefaults,
+    /// Relay profile for accounts that have not chosen one
+    pub relay_profile: Option<String>,
+    /// Custom relay profiles by name; types left out use the profile's nostr relays
+    pub relay_profiles: BTreeMap<String, RelayDefaults>,
+    /// Blossom server used for encrypted file attachments
+    pub media_server: Option<String>,
+    /// File the configuration was read from, if any
+    #[serde(skip)]
+    pub source: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RelayDefaults {
+    pub nostr: Option<Vec<String>>,
+    pub inbox: Option<Vec<String>>,
+    pub key_package: Option<Vec<String>>,
+}
+
+impl Config {
+    /// Load the configuration from the first file on the search path:
+    /// the explicit `--config` path, `$WHITENOISE_CLI_CONFIG`,
+    /// `./.whitenoise-cli/config.{toml,json}`, then
+    /// `<config dir>/whitenoise-cli/config.{toml,json}`.
+    pub fn load(explicit_path: Option<&str>) -> Result<Self> {
+        let mut config = match Self::locate(explicit_path)? {
+            Some(path) => Self::from_file(&path)?,
+            None => Self::default(),
+        };
+        config.apply_env_overrides()?;
+        Ok(config)
+    }
+
+    fn locate(explicit_path: Option<&str>) -> Result<Option<PathBuf>> {
+        if let Some(path) = explicit_path {
+            let path = PathBuf::from(path);
+            if !path.exists() {
+                return Err(anyhow::anyhow!("Config file not found: {}", path.display()));
+            }
+            return Ok(Some(path));
+        }
+
+        if let Ok(path) = std::env::var(CONFIG_ENV) {
+            let path = PathBuf::from(path);
+            if !path.exists() {
+                return Err(anyhow::anyhow!("Config file from {} not found: {}", CONFIG_ENV, path.display()));
+            }
+            return Ok(Some(path));
+        }
+
+        let mut search_dirs = vec![std::env::current_dir()?.join(".whitenoise-cli")];
+        if let Some(config_dir) = dirs::config_dir() {
+            search_dirs.push(config_dir.join("whitenoise-cli"));
+        }
+
+        Ok(search_dirs
+            .iter()
+            .flat_map(|dir| CONFIG_FILE_NAMES.iter().map(move |name| dir.join(name)))
+            .find(|path| path.exists()))
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .map_err(|e| anyhow::anyhow!("Failed to read config file {}: {}", path.display(), e))?;
+
+        let mut config = Self::parse(&content, path.extension().and_then(|ext| ext.to_str()))
+            .map_err(|e| anyhow::anyhow!("Invalid config file {}: {}", path.display(), e))?;
+
+        // Relative directories are resolved against the config file location
+        let base = path.parent().unwrap_or_else(|| Path::new("."));
+        config.data_dir = config.data_dir.map(|dir| base.join(dir));
+        config.logs_dir = config.logs_dir.map(|dir| base.join(dir));
+        config.state_dir = config.state_dir.map(|dir| base.join(dir));
+        config.source = Some(path.to_path_buf());
+        Ok(config)
+    }
+
+    fn parse(content: &str, extension: Option<&str>) -> Result<Self> {
+        match extension {
+            Some("json") => Ok(serde_json::from_str(content)?),
+            _ => Ok(toml::from_str(content)?),
+        }
+    }
+
+    fn apply_env_overrides(&mut self) -> Result<()> {
+        if let Ok(dir) = std::env::var("WHITENOISE_CLI_DATA_DIR") {
+            self.data_dir = Some(PathBuf::from(dir));
+        }
+        if let Ok(dir) = std::env::var("WHITENOISE_CLI_LOGS_DIR") {
+            self.logs_dir = Some(PathBuf::from(dir));
+        }
+        if let Ok(dir) = std::env::var("WHITENOISE_CLI_STATE_DIR") {
+            self.state_dir = Some(PathBuf::from(dir));
+        }
+        if let Ok(filter) = std::env::var("WHITENOISE_CLI_LOG") {
+            self.log_filter = Some(filter);
+        }
+        if let Ok(output) = std::env::var("WHITENOISE_CLI_OUTPUT") {
+            let format = OutputFormat::from_str(&output, true)
+                .map_err(|_| anyhow::anyhow!("Invalid WHITENOISE_CLI_OUTPUT: {}. Use 'human', 'json', or 'yaml'", output))?;
+            self.output = Some(format);
+        }
+        if let Ok(account) = std::env::var("WHITENOISE_CLI_ACCOUNT") {
+            self.default_account = Some(account);
+        }
+        if let Ok(server) = std::env::var("WHITENOISE_CLI_MEDIA_SERVER") {
+            self.media_server = Some(server);
+        }
+        if let Ok(profile) = std::env::var("WHITENOISE_CLI_RELAY_PROFILE") {
+            self.relay_profile = Some(profile);
+        }
+        Ok(())
+    }
+
+    fn base_dir() -> PathBuf {
+        dirs::data_dir()
+            .unwrap_or_else(|| PathBuf::from("."))
+            .join("whitenoise-cli")
+    }
+
+    pub fn data_dir(&self) -> PathBuf {
+        self.data_dir.clone().unwrap_or_else(|| Self::base_dir().join("data"))
+    }
+
+    pub fn logs_dir(&self) -> PathBuf {
+        self.logs_dir.clone().unwrap_or_else(|| Self::base_dir().join("logs"))
+    }
+
+    pub fn state_dir(&self) -> Result<PathBuf> {
+        match &self.state_dir {
+            Some(dir) => Ok(dir.clone()),
+            None => Ok(std::env::current_dir()?.join(".whitenoise-cli")),
+        }
+    }
+
+    /// The configured tracing filter, or the mode-specific default
+    pub fn log_filter_or(&self, default: &str) -> String {
+        self.log_filter.clone().unwrap_or_else(|| default.to_string())
+    }
+
+    pub fn media_server(&self) -> String {
+        self.media_server.clone().unwrap_or_else(|| DEFAULT_MEDIA_SERVER.to_string())
+    }
+
+    /// Built-in relay defaults with any per-type overrides applied
+    pub fn relay_config(&self) -> RelayConfig {
+        let mut relay_config = RelayConfig::default();
+        if let Some(relays) = &self.relays.nostr {
+            relay_config.nostr_relays = relays.clone();
+        }
+        if let Some(relays) = &self.relays.inbox {
+            relay_config.inbox_relays = relays.clone();
+        }
+        if let Some(relays) = &self.relays.key_package {
+            relay_config.key_package_relays = relays.clone();
+        }
+        relay_config
+    }
+
+    /// Relays of the named profile. Custom profiles from the config file
+    /// take precedence over built-in ones of the same name.
+    pub fn relay_profile(&self, name: &str) -> Result<RelayConfig> {
+        if let Some(custom) = self.relay_profiles.get(name) {
+            let nostr_relays = custom.nostr.clone().ok_or_else(|| {
+                CliError::Config(format!("Relay profile {} has no nostr relays", name))
+            })?;
+            return Ok(RelayConfig {
+                profile: name.to_string(),
+                inbox_relays: custom.inbox.clone().unwrap_or_else(|| nostr_relays.clone()),
+                key_package_relays: custom.key_package.clone().unwrap_or_else(|| nostr_relays.clone()),
+                nostr_relays,
+            });
+        }
+        if name == DEFAULT_RELAY_PROFILE {
+            return Ok(self.relay_config());
+        }
+        RelayConfig::builtin(name).ok_or_else(|| {
+            CliError::NotFound(format!(
+                "Unknown relay profile {}; available: {}", name, self.relay_profile_names().join(", ")
+            )).into()
+        })
+    }
+
+    /// Built-in and custom relay profile names
+    pub fn relay_profile_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = BUILTIN_RELAY_PROFILES.iter().map(|name| name.to_string()).collect();
+        for name in self.relay_profiles.keys() {
+            if !names.contains(name) {
+                names.push(name.clone());
+            }
+        }
+        names
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_toml_and_json() {
+        let toml = r#"
+            data_dir = "/var/lib/whitenoise"
+            output = "json"
+            default_account = "abcd"
+
+            [relays]
+            nostr = ["ws://localhost:10547"]
+        "#;
+        let config = Config::parse(toml, Some("toml")).unwrap();
+        assert_eq!(config.data_dir(), PathBuf::from("/var/lib/whitenoise"));
+        assert!(config.output == Some(OutputFormat::Json));
+        assert_eq!(config.relay_config().nostr_relays, vec!["ws://localhost:10547".to_string()]);
+        assert_eq!(config.relay_config().inbox_relays, RelayConfig::default().inbox_relays);
+
+        let json = r#"{ "log_filter": "debug", "relays": { "inbox": [] } }"#;
+        let config = Config::parse(json, Some("json")).unwrap();
+        assert_eq!(config.log_filter_or("error"), "debug");
+        assert!(config.relay_config().inbox_relays.is_empty());
+    }
+
+    #[test]
+    fn test_unknown_keys_rejected() {
+        assert!(Config::parse("datadir = \"/tmp\"", Some("toml")).is_err());
+    }
+
+    #[test]
+    fn test_relay_profiles() {
+        let toml = r#"
+            relay_profile = "work"
+
+            [relays]
+            nostr = ["wss://override.example"]
+
+            [relay_profiles.work]
+            nostr = ["wss://relay.work.example"]
+            inbox = ["wss://inbox.work.example"]
+        "#;
+        let config = Config::parse(toml, Some("toml")).unwrap();
+
+        let work = config.relay_profile("work").unwrap();
+        assert_eq!(work.nostr_relays, vec!["wss://relay.work.example".to_string()]);
+        assert_eq!(work.inbox_relays, vec!["wss://inbox.work.example".to_string()]);
+        assert_eq!(work.key_package_relays, work.nostr_relays);
+
+        // Overrides only apply to the default profile
+        assert_eq!(config.relay_profile("default").unwrap().nostr_relays, vec!["wss://override.example".to_string()]);
+        assert_eq!(config.relay_profile("local-dev").unwrap().nostr_relays, vec!["ws://localhost:10547".to_string()]);
+        assert!(!config.relay_profile("public").unwrap().nostr_relays.iter().any(|r| r.contains("localhost")));
+        assert!(config.relay_profile("staging").is_err());
+        assert_eq!(config.relay_profile_names(), vec!["default", "local-dev", "public", "work"]);
+    }
+}
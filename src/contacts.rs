@@ -3,6 +3,9 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use whitenoise::{PublicKey, Metadata, Whitenoise, Tag, RelayUrl, Account};
 
+use crate::identifiers::Identity;
+use crate::relays::NetworkPolicy;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Contact {
     pub name: String,
@@ -136,28 +139,25 @@ impl ContactManager {
             .map_err(|e| anyhow::anyhow!("Failed to send direct message: {:?}", e))
     }
 
-    pub async fn add(&mut self, name: String, public_key: String) -> Result<()> {
-        // Parse the public key to validate it
-        let pubkey = if public_key.starts_with("npub") {
-            // Use parse method for npub format
-            PublicKey::parse(&public_key)
-                .map_err(|e| anyhow::anyhow!("Invalid npub format: {:?}", e))?
-        } else {
-            PublicKey::from_hex(&public_key)
-                .map_err(|e| anyhow::anyhow!("Invalid hex format: {:?}", e))?
-        };
+    /// Add a contact, looking up its metadata on the identifier's relay
+    /// hints and `lookup_relays` (those of the active relay profile)
+    pub async fn add(&mut self, name: String, identity: Identity, lookup_relays: Vec<RelayUrl>) -> Result<()> {
+        let pubkey = identity.pubkey;
 
         // Try to fetch metadata for this contact
         let whitenoise = Whitenoise::get_instance()
             .map_err(|e| anyhow::anyhow!("Failed to get WhiteNoise instance: {:?}", e))?;
 
-        // Include local relay for testing plus public relays
-        let nip65_relays = vec![
-            RelayUrl::parse("ws://localhost:10547")?,
-            RelayUrl::parse("wss://relay.damus.io")?,
-            RelayUrl::parse("wss://relay.primal.net")?,
-            RelayUrl::parse("wss://nos.lol")?,
-        ];
+        // Relay hints outside --offline/--relays are dropped rather than refused
+        let policy = NetworkPolicy::current();
+        let mut nip65_relays: Vec<RelayUrl> = identity.relays.into_iter()
+            .filter(|relay| policy.allows(&relay.to_string()))
+            .collect();
+        for relay in lookup_relays {
+            if !nip65_relays.contains(&relay) {
+                nip65_relays.push(relay);
+            }
+        }
         
         let metadata = whitenoise.fetch_metadata_from(nip65_relays, pubkey).await
             .map_err(|e| anyhow::anyhow!("Failed to fetch metadata: {:?}", e))?;
@@ -0,0 +1,106 @@
+use thiserror::Error;
+
+/// Failures a script may want to branch on. Each variant has a stable
+/// `code` for the JSON envelope and its own process exit status; errors
+/// that are not a `CliError` are reported as `internal`.
+#[derive(Debug, Error)]
+pub enum CliError {
+    #[error("{0}")]
+    Internal(String),
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error("No account logged in")]
+    NotLoggedIn,
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    InvalidKey(String),
+    #[error("{0}")]
+    PermissionDenied(String),
+    #[error("{0}")]
+    Relay(String),
+    #[error("{0}")]
+    Mls(String),
+    #[error("{0}")]
+    KeyStore(String),
+    #[error("{0}")]
+    Config(String),
+}
+
+impl CliError {
+    pub fn code(&self) -> &'static str {
+        match self {
+            CliError::Internal(_) => "internal",
+            CliError::InvalidInput(_) => "invalid_input",
+            CliError::NotLoggedIn => "not_logged_in",
+            CliError::NotFound(_) => "not_found",
+            CliError::InvalidKey(_) => "invalid_key",
+            CliError::PermissionDenied(_) => "permission_denied",
+            CliError::Relay(_) => "relay_failure",
+            CliError::Mls(_) => "mls_failure",
+            CliError::KeyStore(_) => "key_store",
+            CliError::Config(_) => "config",
+        }
+    }
+
+    /// Process exit status; 2 matches clap's own usage errors
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            CliError::Internal(_) => 1,
+            CliError::InvalidInput(_) => 2,
+            CliError::NotLoggedIn => 3,
+            CliError::NotFound(_) => 4,
+            CliError::InvalidKey(_) => 5,
+            CliError::PermissionDenied(_) => 6,
+            CliError::Relay(_) => 7,
+            CliError::Mls(_) => 8,
+            CliError::KeyStore(_) => 9,
+            CliError::Config(_) => 10,
+        }
+    }
+}
+
+/// A failure that still has a result to show, such as the probe report of
+/// an unreachable relay; it is reported in the envelope's `data`
+#[derive(Debug, Error)]
+#[error("{error}")]
+pub struct FailureWithData {
+    pub error: CliError,
+    pub data: serde_json::Value,
+}
+
+impl From<anyhow::Error> for CliError {
+    fn from(error: anyhow::Error) -> Self {
+        match error.downcast::<CliError>() {
+            Ok(error) => error,
+            Err(error) => match error.downcast::<FailureWithData>() {
+                Ok(failure) => failure.error,
+                Err(error) => CliError::Internal(error.to_string()),
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_downcast_from_anyhow() {
+        let error: anyhow::Error = CliError::NotFound("Group not found".to_string()).into();
+        let error = CliError::from(error);
+        assert_eq!(error.code(), "not_found");
+        assert_eq!(error.exit_code(), 4);
+        assert_eq!(error.to_string(), "Group not found");
+
+        let error: anyhow::Error = FailureWithData {
+            error: CliError::Relay("unreachable".to_string()),
+            data: serde_json::json!({ "reachable": false }),
+        }.into();
+        assert_eq!(CliError::from(error).exit_code(), 7);
+
+        let error = CliError::from(anyhow::anyhow!("boom"));
+        assert_eq!(error.code(), "internal");
+        assert_eq!(error.exit_code(), 1);
+    }
+}
@@ -0,0 +1,390 @@
+use anyhow::Result;
+use nostr::secp256k1::{schnorr::Signature, Message, Secp256k1, XOnlyPublicKey};
+use nostr::Keys;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use whitenoise::ChatMessage;
+
+use crate::errors::CliError;
+use crate::groups::GroupData;
+
+/// Identifies a file as a conversation archive
+pub const ARCHIVE_FORMAT: &str = "whitenoise-cli/conversation-archive";
+/// Archive layout version; imports reject versions they do not know
+pub const ARCHIVE_VERSION: u32 = 1;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum)]
+pub enum ExportFormat {
+    /// Signed archive, importable with `history import`
+    Json,
+    Markdown,
+    Html,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Json => "json",
+            ExportFormat::Markdown => "md",
+            ExportFormat::Html => "html",
+        }
+    }
+}
+
+/// Decrypted history of one group, as written by `group export`.
+///
+/// The exporting account signs the SHA-256 of the archive serialized with
+/// an empty `signature` (BIP-340 Schnorr, as for Nostr events), so an
+/// import can tell the archive has not been altered since export.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConversationArchive {
+    pub format: String,
+    pub version: u32,
+    pub group: ArchivedGroup,
+    /// Hex public key of the exporting account
+    pub exported_by: String,
+    pub exported_at: u64,
+    pub messages: Vec<ArchivedMessage>,
+    #[serde(default)]
+    pub signature: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedGroup {
+    pub id: String,
+    pub name: String,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedMessage {
+    pub id: String,
+    pub author: String,
+    pub author_name: Option<String>,
+    pub content: String,
+    pub created_at: u64,
+    pub kind: u16,
+    pub reply_to_id: Option<String>,
+    pub reactions: Vec<ArchivedReaction>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedReaction {
+    pub emoji: String,
+    pub count: usize,
+}
+
+impl ConversationArchive {
+    /// Archive the visible history of `group`; deleted messages are left out
+    pub fn new(
+        group: &GroupData,
+        messages: &[ChatMessage],
+        names: &HashMap<String, String>,
+        exported_by: &str,
+    ) -> Self {
+        let messages = messages
+            .iter()
+            .filter(|message| !message.is_deleted)
+            .map(|message| {
+                let author = message.author.to_hex();
+                let mut reactions: Vec<ArchivedReaction> = message
+                    .reactions
+                    .by_emoji
+                    .values()
+                    .map(|reaction| ArchivedReaction {
+                        emoji: reaction.emoji.clone(),
+                        count: reaction.count,
+                    })
+                    .collect();
+                reactions.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.emoji.cmp(&b.emoji)));
+                ArchivedMessage {
+                    id: message.id.clone(),
+                    author_name: names.get(&author).cloned(),
+                    author,
+                    content: message.content.clone(),
+                    created_at: message.created_at.as_u64(),
+                    kind: message.kind,
+                    reply_to_id: message.reply_to_id.clone(),
+                    reactions,
+                }
+            })
+            .collect();
+
+        Self {
+            format: ARCHIVE_FORMAT.to_string(),
+            version: ARCHIVE_VERSION,
+            group: ArchivedGroup {
+                id: group.mls_group_id.clone(),
+                name: group.name.clone(),
+                description: group.description.clone(),
+            },
+            exported_by: exported_by.to_string(),
+            exported_at: chrono::Utc::now().timestamp() as u64,
+            messages,
+            signature: None,
+        }
+    }
+
+    /// Local name for an imported archive, unique per group and export
+    pub fn id(&self) -> String {
+        format!(
+            "{}-{}",
+            &self.group.id[..self.group.id.len().min(16)],
+            self.exported_at
+        )
+    }
+
+    fn digest(&self) -> Result<Message> {
+        let unsigned = Self {
+            signature: None,
+            ..self.clone()
+        };
+        let hash: [u8; 32] = Sha256::digest(serde_json::to_vec(&unsigned)?).into();
+        Ok(Message::from_digest(hash))
+    }
+
+    /// Sign with the exporting account's private key (nsec or hex)
+    pub fn sign(&mut self, private_key: &str) -> Result<()> {
+        let keys = Keys::parse(private_key).map_err(|e| {
+            CliError::InvalidKey(format!(
+                "Private key must be an nsec or 64 hex characters: {}",
+                e
+            ))
+        })?;
+        if keys.public_key().to_hex() != self.exported_by {
+            return Err(CliError::InvalidKey(
+                "Private key does not belong to the exporting account".to_string(),
+            )
+            .into());
+        }
+
+        self.signature = Some(keys.sign_schnorr(&self.digest()?).to_string());
+        Ok(())
+    }
+
+    /// Check the format, version and signature of an archive being imported
+    pub fn verify(&self) -> Result<()> {
+        if self.format != ARCHIVE_FORMAT {
+            return Err(CliError::InvalidInput("Not a conversation archive".to_string()).into());
+        }
+        if self.version != ARCHIVE_VERSION {
+            return Err(CliError::InvalidInput(format!(
+                "Unsupported archive version {} (this build reads version {})",
+                self.version, ARCHIVE_VERSION
+            ))
+            .into());
+        }
+        // The group id names the imported file, so it must not carry path characters
+        if self.group.id.is_empty() || !self.group.id.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(
+                CliError::InvalidInput("Archive has an invalid group id".to_string()).into(),
+            );
+        }
+
+        let invalid = |reason: &str| {
+            CliError::InvalidInput(format!("Archive signature is invalid: {}", reason))
+        };
+        let signature = self
+            .signature
+            .as_deref()
+            .ok_or_else(|| invalid("archive is unsigned"))?;
+        let signature: Signature = signature
+            .parse()
+            .map_err(|_| invalid("malformed signature"))?;
+        let public_key: XOnlyPublicKey = self
+            .exported_by
+            .parse()
+            .map_err(|_| invalid("malformed exporter key"))?;
+        Secp256k1::verification_only()
+            .verify_schnorr(&signature, &self.digest()?, &public_key)
+            .map_err(|_| invalid("contents were modified after export").into())
+    }
+
+    fn author_label(&self, message: &ArchivedMessage) -> String {
+        message
+            .author_name
+            .clone()
+            .unwrap_or_else(|| message.author[..8].to_string())
+    }
+
+    fn parent(&self, message: &ArchivedMessage) -> Option<&ArchivedMessage> {
+        let reply_to = message.reply_to_id.as_ref()?;
+        self.messages.iter().find(|m| &m.id == reply_to)
+    }
+
+    pub fn to_markdown(&self) -> String {
+        let mut out = format!("# {}\n\n", self.group.name);
+        if !self.group.description.is_empty() {
+            out.push_str(&format!("{}\n\n", self.group.description));
+        }
+        out.push_str(&format!(
+            "_Exported {} · {} messages_\n\n---\n\n",
+            format_time(self.exported_at),
+            self.messages.len()
+        ));
+
+        for message in &self.messages {
+            out.push_str(&format!(
+                "**{}** · {}\n\n",
+                self.author_label(message),
+                format_time(message.created_at)
+            ));
+            if let Some(parent) = self.parent(message) {
+                out.push_str(&format!(
+                    "> ↳ {}: {}\n\n",
+                    self.author_label(parent),
+                    excerpt(&parent.content)
+                ));
+            }
+            out.push_str(&message.content);
+            out.push_str("\n\n");
+            if !message.reactions.is_empty() {
+                out.push_str(&format!("_{}_\n\n", reaction_summary(&message.reactions)));
+            }
+        }
+        out
+    }
+
+    pub fn to_html(&self) -> String {
+        let mut out = String::from("<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n");
+        out.push_str(&format!(
+            "<title>{}</title>\n",
+            escape_html(&self.group.name)
+        ));
+        out.push_str("<style>body{font-family:sans-serif;max-width:48em;margin:auto}.meta,.reply,.reactions{color:#666;font-size:.9em}.message{margin:1em 0}.content{white-space:pre-wrap}</style>\n");
+        out.push_str("</head>\n<body>\n");
+        out.push_str(&format!("<h1>{}</h1>\n", escape_html(&self.group.name)));
+        if !self.group.description.is_empty() {
+            out.push_str(&format!(
+                "<p>{}</p>\n",
+                escape_html(&self.group.description)
+            ));
+        }
+        out.push_str(&format!(
+            "<p class=\"meta\">Exported {} · {} messages</p>\n",
+            format_time(self.exported_at),
+            self.messages.len()
+        ));
+
+        for message in &self.messages {
+            out.push_str(&format!(
+                "<div class=\"message\" id=\"{}\">\n",
+                escape_html(&message.id)
+            ));
+            out.push_str(&format!(
+                "<div class=\"meta\"><strong>{}</strong> · {}</div>\n",
+                escape_html(&self.author_label(message)),
+                format_time(message.created_at)
+            ));
+            if let Some(parent) = self.parent(message) {
+                out.push_str(&format!(
+                    "<div class=\"reply\"><a href=\"#{}\">↳ {}: {}</a></div>\n",
+                    escape_html(&parent.id),
+                    escape_html(&self.author_label(parent)),
+                    escape_html(&excerpt(&parent.content))
+                ));
+            }
+            out.push_str(&format!(
+                "<div class=\"content\">{}</div>\n",
+                escape_html(&message.content)
+            ));
+            if !message.reactions.is_empty() {
+                out.push_str(&format!(
+                    "<div class=\"reactions\">{}</div>\n",
+                    escape_html(&reaction_summary(&message.reactions))
+                ));
+            }
+            out.push_str("</div>\n");
+        }
+        out.push_str("</body>\n</html>\n");
+        out
+    }
+}
+
+fn format_time(timestamp: u64) -> String {
+    chrono::DateTime::from_timestamp(timestamp as i64, 0)
+        .unwrap_or_default()
+        .format("%Y-%m-%d %H:%M UTC")
+        .to_string()
+}
+
+fn excerpt(content: &str) -> String {
+    let first_line = content.lines().next().unwrap_or_default();
+    if first_line.chars().count() > 60 {
+        format!("{}…", first_line.chars().take(60).collect::<String>())
+    } else {
+        first_line.to_string()
+    }
+}
+
+fn reaction_summary(reactions: &[ArchivedReaction]) -> String {
+    reactions
+        .iter()
+        .map(|reaction| format!("{} {}", reaction.emoji, reaction.count))
+        .collect::<Vec<_>>()
+        .join("  ")
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // NIP-19 test vector
+    const NSEC: &str = "nsec1vl029mgpspedva04g90vltkh6fvh240zqtv9k0t9af8935ke9laqsnlfe5";
+    const SECRET_HEX: &str = "67dea2ed018072d675f5415ecfaed7d2597555e202d85b3d65ea4e58d2d92ffa";
+
+    #[test]
+    fn test_sign_and_verify_archive() {
+        let exported_by = Keys::parse(SECRET_HEX).unwrap().public_key().to_hex();
+
+        let mut archive = ConversationArchive {
+            format: ARCHIVE_FORMAT.to_string(),
+            version: ARCHIVE_VERSION,
+            group: ArchivedGroup {
+                id: "ab".repeat(32),
+                name: "Team <3".to_string(),
+                description: String::new(),
+            },
+            exported_by,
+            exported_at: 1700000000,
+            messages: vec![ArchivedMessage {
+                id: "cd".repeat(32),
+                author: "ef".repeat(32),
+                author_name: Some("Alice".to_string()),
+                content: "ship it".to_string(),
+                created_at: 1700000000,
+                kind: 9,
+                reply_to_id: None,
+                reactions: vec![ArchivedReaction {
+                    emoji: "🚀".to_string(),
+                    count: 2,
+                }],
+            }],
+            signature: None,
+        };
+        assert!(archive.verify().is_err());
+
+        assert!(archive.sign(&NSEC.replace("fe5", "fe6")).is_err());
+        archive.sign(NSEC).unwrap();
+        let json = serde_json::to_string_pretty(&archive).unwrap();
+        let imported: ConversationArchive = serde_json::from_str(&json).unwrap();
+        imported.verify().unwrap();
+
+        let mut tampered = imported.clone();
+        tampered.messages[0].content = "don't ship it".to_string();
+        assert!(tampered.verify().is_err());
+
+        assert!(archive.to_html().contains("<title>Team &lt;3</title>"));
+        assert!(archive
+            .to_markdown()
+            .contains("**Alice** · 2023-11-14 22:13 UTC\n\nship it\n\n_🚀 2_"));
+    }
+}
@@ -1,45 +1,135 @@
 use anyhow::Result;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine as _};
 use console::style;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::time::Duration;
 use whitenoise::{
-    Account, Group, GroupId, GroupState, GroupType, NostrGroupConfigData, PublicKey, Whitenoise,
-    MessageWithTokens, ChatMessage,
+    Account, Group, GroupId, GroupState, GroupType, NostrGroupConfigData, NostrGroupDataUpdate,
+    PublicKey, Tag, Whitenoise, MessageWithTokens, ChatMessage, Welcome, WelcomeState,
 };
 
+use crate::cli::{PageArgs, PageInfo};
+use crate::errors::CliError;
+use crate::media::{MediaAttachment, MediaSecret};
+use crate::relays::NetworkPolicy;
+use crate::search::parse_time_bound;
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GroupData {
     pub mls_group_id: String,
     pub nostr_group_id: String,
     pub name: String,
     pub description: String,
+    #[serde(default)]
+    pub image_url: Option<String>,
     pub admin_pubkeys: Vec<String>,
     pub last_message_id: Option<String>,
     pub last_message_at: Option<u64>,
     pub group_type: GroupType,
     pub epoch: u64,
     pub state: GroupState,
+    /// Hidden locally with `group archive`; never shared with other members
+    #[serde(default)]
+    pub archived: bool,
+}
+
+/// Which groups `group list` and the interactive group menu show.
+/// Without a filter every unarchived group is listed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, clap::ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GroupFilter {
+    Active,
+    Inactive,
+    Archived,
+}
+
+impl GroupFilter {
+    pub fn label(filter: Option<GroupFilter>) -> &'static str {
+        match filter {
+            None => "unarchived",
+            Some(GroupFilter::Active) => "active",
+            Some(GroupFilter::Inactive) => "inactive",
+            Some(GroupFilter::Archived) => "archived",
+        }
+    }
 }
 
 impl GroupData {
+    pub fn matches(&self, filter: Option<GroupFilter>) -> bool {
+        match filter {
+            None => !self.archived,
+            Some(GroupFilter::Active) => !self.archived && self.state == GroupState::Active,
+            Some(GroupFilter::Inactive) => !self.archived && self.state != GroupState::Active,
+            Some(GroupFilter::Archived) => self.archived,
+        }
+    }
+
     pub fn from_group(group: &Group) -> Self {
         Self {
             mls_group_id: hex::encode(group.mls_group_id.as_slice()),
             nostr_group_id: hex::encode(group.nostr_group_id),
             name: group.name.clone(),
             description: group.description.clone(),
+            image_url: group.image_url.clone(),
             admin_pubkeys: group.admin_pubkeys.iter().map(|pk| pk.to_hex()).collect(),
             last_message_id: group.last_message_id.map(|id| id.to_hex()),
             last_message_at: group.last_message_at.map(|at| at.as_u64()),
             group_type: group.group_type,
             epoch: group.epoch,
             state: group.state,
+            archived: false,
         }
     }
 }
 
-#[derive(Debug, Clone)]
+/// Current membership of a group, reported after membership changes
+#[derive(Debug, Clone, Serialize)]
+pub struct GroupMembership {
+    pub group_id: String,
+    pub members: Vec<String>,
+    pub admins: Vec<String>,
+    pub epoch: Option<u64>,
+}
+
+/// A pending or past invitation (MLS welcome) to join a group
+#[derive(Debug, Clone, Serialize)]
+pub struct InviteData {
+    pub id: String,
+    pub group_id: String,
+    pub nostr_group_id: String,
+    pub group_name: String,
+    pub group_description: String,
+    pub invited_by: String,
+    pub member_count: u32,
+    pub admins: Vec<String>,
+    pub relays: Vec<String>,
+    pub state: String,
+    pub received_at: u64,
+}
+
+impl InviteData {
+    pub fn from_welcome(welcome: &Welcome) -> Self {
+        Self {
+            id: welcome.id.to_hex(),
+            group_id: hex::encode(welcome.mls_group_id.as_slice()),
+            nostr_group_id: hex::encode(welcome.nostr_group_id),
+            group_name: welcome.group_name.clone(),
+            group_description: welcome.group_description.clone(),
+            invited_by: welcome.welcomer.to_hex(),
+            member_count: welcome.member_count,
+            admins: welcome.group_admin_pubkeys.iter().map(|pk| pk.to_hex()).collect(),
+            relays: welcome.group_relays.iter().map(|url| url.to_string()).collect(),
+            state: format!("{:?}", welcome.state).to_lowercase(),
+            received_at: welcome.event.created_at.as_u64(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
 pub struct MessageData {
     pub id: String,
+    #[serde(rename = "author")]
     pub pubkey: String,
     pub content: String,
     pub created_at: u64,
@@ -47,6 +137,8 @@ pub struct MessageData {
     pub reply_to_id: Option<String>,
     pub is_deleted: bool,
     pub kind: u16,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<MediaAttachment>,
 }
 
 impl MessageData {
@@ -60,8 +152,343 @@ impl MessageData {
             reply_to_id: message.reply_to_id.clone(),
             is_deleted: message.is_deleted,
             kind: message.kind,
+            attachments: MediaAttachment::from_tags(&message.tags),
+        }
+    }
+}
+
+/// Inner event kinds this client sends inside MLS group messages
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageKind {
+    /// NIP-C7 chat message, what the Flutter client sends and renders
+    Chat,
+    /// Plain kind 1 note, accepted by `--kind 1` for older clients
+    TextNote,
+    Reaction,
+    Deletion,
+}
+
+impl MessageKind {
+    pub fn as_u16(self) -> u16 {
+        match self {
+            MessageKind::Chat => 9,
+            MessageKind::TextNote => 1,
+            MessageKind::Reaction => 7,
+            MessageKind::Deletion => 5,
         }
     }
+
+    /// Kind for `message send --kind`; reactions and deletions need their own commands
+    pub fn for_text(kind: u16) -> Result<Self> {
+        match kind {
+            9 => Ok(MessageKind::Chat),
+            1 => Ok(MessageKind::TextNote),
+            7 => Err(CliError::InvalidInput("Kind 7 reactions are sent with `message react`".to_string()).into()),
+            5 => Err(CliError::InvalidInput("Kind 5 deletions are sent with `message delete`".to_string()).into()),
+            other => Err(CliError::InvalidInput(format!("Unsupported message kind {}. Use 9 (chat) or 1 (text note)", other)).into()),
+        }
+    }
+}
+
+/// The message a reply, reaction or deletion points at
+#[derive(Debug, Clone)]
+pub struct MessageRef {
+    pub id: String,
+    pub author: PublicKey,
+    pub kind: u16,
+}
+
+impl From<&ChatMessage> for MessageRef {
+    fn from(message: &ChatMessage) -> Self {
+        Self {
+            id: message.id.clone(),
+            author: message.author,
+            kind: message.kind,
+        }
+    }
+}
+
+/// A message to send to a group; `GroupManager::send_message_to_group`
+/// turns it into the inner event kind, content and tags.
+#[derive(Debug, Clone)]
+pub enum OutgoingMessage {
+    Text { content: String, kind: MessageKind },
+    Reply { content: String, parent: MessageRef },
+    Reaction { emoji: String, target: MessageRef },
+    Deletion { target: MessageRef, reason: Option<String> },
+    Media { caption: String, attachment: MediaAttachment },
+}
+
+impl OutgoingMessage {
+    pub fn text(content: impl Into<String>) -> Self {
+        OutgoingMessage::Text { content: content.into(), kind: MessageKind::Chat }
+    }
+
+    pub fn reply(parent: &ChatMessage, content: impl Into<String>) -> Self {
+        OutgoingMessage::Reply { content: content.into(), parent: parent.into() }
+    }
+
+    pub fn reaction(target: &ChatMessage, emoji: impl Into<String>) -> Self {
+        OutgoingMessage::Reaction { emoji: emoji.into(), target: target.into() }
+    }
+
+    pub fn deletion(target: &ChatMessage, reason: Option<String>) -> Self {
+        OutgoingMessage::Deletion { target: target.into(), reason }
+    }
+
+    pub fn media(attachment: MediaAttachment, caption: impl Into<String>) -> Self {
+        OutgoingMessage::Media { caption: caption.into(), attachment }
+    }
+
+    pub fn kind(&self) -> MessageKind {
+        match self {
+            OutgoingMessage::Text { kind, .. } => *kind,
+            // Replies stay in the conversation's kind
+            OutgoingMessage::Reply { parent, .. } if parent.kind == MessageKind::TextNote.as_u16() => MessageKind::TextNote,
+            OutgoingMessage::Reply { .. } => MessageKind::Chat,
+            OutgoingMessage::Reaction { .. } => MessageKind::Reaction,
+            OutgoingMessage::Deletion { .. } => MessageKind::Deletion,
+            OutgoingMessage::Media { .. } => MessageKind::Chat,
+        }
+    }
+
+    /// e/p/k tags referencing the target message, or the attachment's imeta tag
+    pub fn tags(&self) -> Result<Vec<Tag>> {
+        let tags = match self {
+            OutgoingMessage::Text { .. } => Vec::new(),
+            OutgoingMessage::Reply { parent, .. } => vec![
+                tag(["e", parent.id.as_str(), "", "reply"])?,
+                tag(["p", parent.author.to_hex().as_str()])?,
+            ],
+            OutgoingMessage::Reaction { target, .. } => vec![
+                tag(["e", target.id.as_str()])?,
+                tag(["p", target.author.to_hex().as_str()])?,
+                tag(["k", target.kind.to_string().as_str()])?,
+            ],
+            OutgoingMessage::Deletion { target, .. } => vec![
+                tag(["e", target.id.as_str()])?,
+                tag(["k", target.kind.to_string().as_str()])?,
+            ],
+            OutgoingMessage::Media { attachment, .. } => vec![attachment.to_tag()?],
+        };
+        Ok(tags)
+    }
+
+    pub fn into_content(self) -> String {
+        match self {
+            OutgoingMessage::Text { content, .. } | OutgoingMessage::Reply { content, .. } => content,
+            OutgoingMessage::Reaction { emoji, .. } => emoji,
+            OutgoingMessage::Deletion { reason, .. } => reason.unwrap_or_default(),
+            OutgoingMessage::Media { caption, .. } => caption,
+        }
+    }
+}
+
+fn tag<const N: usize>(parts: [&str; N]) -> Result<Tag> {
+    Tag::parse(parts).map_err(|e| anyhow::anyhow!("Failed to build tag: {:?}", e))
+}
+
+/// How often streaming commands poll for new group messages
+pub const FEED_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// One line of a streaming message feed (NDJSON)
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum FeedEvent {
+    Message {
+        /// Receiving account, set when watching several accounts
+        #[serde(skip_serializing_if = "Option::is_none")]
+        account: Option<String>,
+        group_id: String,
+        #[serde(flatten)]
+        message: MessageData,
+    },
+    Sent {
+        group_id: String,
+        id: String,
+        content: String,
+    },
+    Error {
+        message: String,
+    },
+    End {
+        reason: String,
+        received: usize,
+        sent: usize,
+    },
+}
+
+impl FeedEvent {
+    pub fn to_line(&self) -> Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+}
+
+/// Tracks which messages have been delivered per group so repeated polls
+/// only yield messages that arrived since the previous poll.
+///
+/// whitenoise has no message subscription, so each poll reads the group
+/// list and only fetches the history of groups whose newest message changed.
+#[derive(Debug, Default)]
+pub struct MessageFeed {
+    seen: HashMap<String, HashSet<String>>,
+    newest: HashMap<String, (Option<String>, Option<u64>)>,
+}
+
+impl MessageFeed {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return the group's messages not delivered yet, oldest first
+    pub async fn poll(
+        &mut self,
+        groups: &GroupManager,
+        account: &Account,
+        group_id: &GroupId,
+    ) -> Result<Vec<ChatMessage>> {
+        let mut polled = self.poll_groups(groups, account, Some(group_id)).await?;
+        match polled.pop() {
+            Some((_, messages)) => messages,
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// Like `poll`, for every group of `account` (or just `only`). Groups
+    /// without new messages are left out; a failed fetch is retried next poll.
+    pub async fn poll_groups(
+        &mut self,
+        groups: &GroupManager,
+        account: &Account,
+        only: Option<&GroupId>,
+    ) -> Result<Vec<(GroupId, Result<Vec<ChatMessage>>)>> {
+        let only = only.map(GroupManager::group_id_to_string);
+        let mut polled = Vec::new();
+        for group in groups.load_groups(account).await? {
+            if only.as_ref().is_some_and(|id| *id != group.mls_group_id) {
+                continue;
+            }
+            let newest = (group.last_message_id.clone(), group.last_message_at);
+            if self.newest.get(&group.mls_group_id) == Some(&newest) {
+                continue;
+            }
+
+            let group_id = GroupManager::group_id_from_string(&group.mls_group_id)?;
+            let messages = self.fetch_new(groups, account, &group_id).await;
+            if messages.is_ok() {
+                self.newest.insert(group.mls_group_id, newest);
+            }
+            polled.push((group_id, messages));
+        }
+        Ok(polled)
+    }
+
+    async fn fetch_new(&mut self, groups: &GroupManager, account: &Account, group_id: &GroupId) -> Result<Vec<ChatMessage>> {
+        let mut messages = groups.fetch_aggregated_messages_for_group(account, group_id).await?;
+        messages.sort_by_key(|m| m.created_at.as_u64());
+
+        let seen = self.seen.entry(GroupManager::group_id_to_string(group_id)).or_default();
+        Ok(messages
+            .into_iter()
+            .filter(|m| !m.is_deleted && seen.insert(m.id.clone()))
+            .collect())
+    }
+
+    /// Record a message as delivered, e.g. one we sent ourselves
+    pub fn mark_seen(&mut self, group_id: &GroupId, message_id: String) {
+        self.seen
+            .entry(GroupManager::group_id_to_string(group_id))
+            .or_default()
+            .insert(message_id);
+    }
+}
+
+/// Messages from other members sent after `last_read`
+pub fn count_unread<M: HistoryEntry>(messages: &[M], own_pubkey: &PublicKey, last_read: Option<u64>) -> usize {
+    messages.iter()
+        .filter(|m| !m.entry_is_deleted() && m.entry_author() != own_pubkey)
+        .filter(|m| last_read.is_none_or(|read_at| m.entry_created_at() > read_at))
+        .count()
+}
+
+/// The parts of a message that paging and unread counts look at
+pub trait HistoryEntry {
+    fn entry_id(&self) -> &str;
+    fn entry_created_at(&self) -> u64;
+    fn entry_author(&self) -> &PublicKey;
+    fn entry_is_deleted(&self) -> bool;
+}
+
+impl HistoryEntry for ChatMessage {
+    fn entry_id(&self) -> &str {
+        &self.id
+    }
+
+    fn entry_created_at(&self) -> u64 {
+        self.created_at.as_u64()
+    }
+
+    fn entry_author(&self) -> &PublicKey {
+        &self.author
+    }
+
+    fn entry_is_deleted(&self) -> bool {
+        self.is_deleted
+    }
+}
+
+/// Select one page of a group's history according to `page`. Filters
+/// apply first, then the page is taken from the newest remaining message
+/// backwards and returned in chronological order.
+///
+/// whitenoise only hands out a group's history fully aggregated (reactions
+/// and deletions folded into their messages), so paging happens here rather
+/// than in the query.
+pub fn paginate_messages<M: HistoryEntry>(mut messages: Vec<M>, page: &PageArgs) -> Result<(Vec<M>, PageInfo)> {
+    messages.sort_by(|a, b| (a.entry_created_at(), a.entry_id()).cmp(&(b.entry_created_at(), b.entry_id())));
+
+    if let Some(since_id) = &page.since_id {
+        let position = messages.iter().position(|m| m.entry_id() == since_id)
+            .ok_or_else(|| CliError::NotFound(format!("Message not found: {}", since_id)))?;
+        messages.drain(..=position);
+    }
+    if let Some(after) = &page.after {
+        let after = parse_time_bound(after, false)?;
+        messages.retain(|m| m.entry_created_at() >= after);
+    }
+    if let Some(before) = &page.before {
+        let before = parse_time_bound(before, false)?;
+        messages.retain(|m| m.entry_created_at() < before);
+    }
+    let total = messages.len();
+
+    if let Some(cursor) = &page.cursor {
+        let (created_at, id) = decode_cursor(cursor)?;
+        messages.retain(|m| (m.entry_created_at(), m.entry_id()) < (created_at, id.as_str()));
+    }
+
+    let end = messages.len().saturating_sub(page.offset);
+    let start = end.saturating_sub(page.limit);
+    let has_more = start > 0;
+    let next_cursor = messages[start..end].first()
+        .filter(|_| has_more)
+        .map(|m| encode_cursor(m.entry_created_at(), m.entry_id()));
+
+    messages.truncate(end);
+    messages.drain(..start);
+    Ok((messages, PageInfo { total, has_more, next_cursor }))
+}
+
+fn encode_cursor(created_at: u64, id: &str) -> String {
+    URL_SAFE_NO_PAD.encode(format!("{}:{}", created_at, id))
+}
+
+fn decode_cursor(cursor: &str) -> Result<(u64, String)> {
+    let invalid = || CliError::InvalidInput(format!("Invalid cursor: {}", cursor));
+    let decoded = URL_SAFE_NO_PAD.decode(cursor).map_err(|_| invalid())?;
+    let decoded = String::from_utf8(decoded).map_err(|_| invalid())?;
+    let (created_at, id) = decoded.split_once(':').ok_or_else(invalid)?;
+    Ok((created_at.parse().map_err(|_| invalid())?, id.to_string()))
 }
 
 pub struct GroupManager {
@@ -76,15 +503,56 @@ impl GroupManager {
     }
 
     pub async fn fetch_groups(&mut self, account: &Account) -> Result<Vec<GroupData>> {
+        let group_data = self.load_groups(account).await?;
+        self.current_groups = group_data.clone();
+        Ok(group_data)
+    }
+
+    /// Like `fetch_groups`, but leaves the cached groups alone; used for
+    /// accounts other than the current one
+    pub async fn load_groups(&self, account: &Account) -> Result<Vec<GroupData>> {
         let whitenoise = Whitenoise::get_instance()
             .map_err(|e| anyhow::anyhow!("Failed to get WhiteNoise instance: {:?}", e))?;
 
         let groups = whitenoise.fetch_groups(account, true).await
             .map_err(|e| anyhow::anyhow!("Failed to fetch groups: {:?}", e))?;
 
-        let group_data: Vec<GroupData> = groups.iter().map(GroupData::from_group).collect();
-        self.current_groups = group_data.clone();
-        Ok(group_data)
+        Ok(groups.iter().map(GroupData::from_group).collect())
+    }
+
+    /// Unread messages across all groups of `account`. `read_markers` maps
+    /// group ids to the newest message the account has read; groups without
+    /// a marker count every message from other members.
+    pub async fn unread_count(&self, account: &Account, read_markers: &BTreeMap<String, u64>) -> Result<usize> {
+        let mut unread = 0;
+        for group in self.load_groups(account).await? {
+            let last_read = read_markers.get(&group.mls_group_id).copied();
+            if group.last_message_at.is_none() || group.last_message_at <= last_read {
+                continue;
+            }
+            let group_id = Self::group_id_from_string(&group.mls_group_id)?;
+            let messages = self.fetch_aggregated_messages_for_group(account, &group_id).await?;
+            unread += count_unread(&messages, &account.pubkey, last_read);
+        }
+        Ok(unread)
+    }
+
+    /// Groups matching `filter`, with `archived` marking the locally archived ones
+    pub async fn list_groups(
+        &mut self,
+        account: &Account,
+        archived: &BTreeSet<String>,
+        filter: Option<GroupFilter>,
+    ) -> Result<Vec<GroupData>> {
+        let groups = self.fetch_groups(account).await?;
+        Ok(groups
+            .into_iter()
+            .map(|mut group| {
+                group.archived = archived.contains(&group.mls_group_id);
+                group
+            })
+            .filter(|group| group.matches(filter))
+            .collect())
     }
 
     pub async fn create_group(
@@ -132,7 +600,7 @@ impl GroupManager {
         })
         .await
         .map_err(|e| anyhow::anyhow!("Task join error: {}", e))?
-        .map_err(|e| anyhow::anyhow!("Failed to create group: {:?}", e))?;
+        .map_err(|e| CliError::Mls(format!("Failed to create group: {:?}", e)))?;
 
         println!("{}", style("✅ Group created successfully!").green());
         let group_data = GroupData::from_group(&group);
@@ -179,7 +647,7 @@ impl GroupManager {
         })
         .await
         .map_err(|e| anyhow::anyhow!("Task join error: {}", e))?
-        .map_err(|e| anyhow::anyhow!("Failed to add members: {:?}", e))?;
+        .map_err(|e| CliError::Mls(format!("Failed to add members: {:?}", e)))?;
 
         println!("{}", style("✅ Members added successfully!").green());
         Ok(())
@@ -208,41 +676,304 @@ impl GroupManager {
         })
         .await
         .map_err(|e| anyhow::anyhow!("Task join error: {}", e))?
-        .map_err(|e| anyhow::anyhow!("Failed to remove members: {:?}", e))?;
+        .map_err(|e| CliError::Mls(format!("Failed to remove members: {:?}", e)))?;
 
         println!("{}", style("✅ Members removed successfully!").green());
         Ok(())
     }
 
+    /// Remove ourselves from a group with a self-remove commit. The last
+    /// admin must promote someone else first so the group stays manageable.
+    pub async fn leave_group(&self, account: &Account, group_id: &GroupId) -> Result<()> {
+        let whitenoise = Whitenoise::get_instance()
+            .map_err(|e| anyhow::anyhow!("Failed to get WhiteNoise instance: {:?}", e))?;
+
+        let members = self.fetch_group_members(account, group_id).await?;
+        let admins = self.fetch_group_admins(account, group_id).await?;
+        if admins == [account.pubkey] && members.len() > 1 {
+            return Err(CliError::InvalidInput(
+                "You are the only admin of this group; promote another member before leaving".to_string(),
+            ).into());
+        }
+
+        println!("{}", style("🚪 Leaving group...").yellow());
+
+        let account_clone = account.clone();
+        let group_id_clone = group_id.clone();
+
+        tokio::task::spawn_blocking(move || {
+            tokio::runtime::Handle::current().block_on(whitenoise.leave_group(&account_clone, &group_id_clone))
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("Task join error: {}", e))?
+        .map_err(|e| CliError::Mls(format!("Failed to leave group: {:?}", e)))?;
+
+        println!("{}", style("✅ Left the group").green());
+        Ok(())
+    }
+
+    /// Return the group's admins, failing unless `account` is one of them
+    pub async fn ensure_admin(&self, account: &Account, group_id: &GroupId) -> Result<Vec<PublicKey>> {
+        let admins = self.fetch_group_admins(account, group_id).await?;
+        if !admins.contains(&account.pubkey) {
+            return Err(CliError::PermissionDenied("Only group admins can change this group".to_string()).into());
+        }
+        Ok(admins)
+    }
+
+    /// Replace the group's admin list via an MLS group context extension commit
+    pub async fn update_group_admins(
+        &self,
+        account: &Account,
+        group_id: &GroupId,
+        admin_pubkeys: Vec<PublicKey>,
+    ) -> Result<()> {
+        println!("{}", style("🛡️  Updating group admins...").yellow());
+
+        let update = NostrGroupDataUpdate {
+            admins: Some(admin_pubkeys),
+            ..Default::default()
+        };
+        self.update_group_data(account, group_id, update).await
+            .map_err(|e| CliError::Mls(format!("Failed to update group admins: {}", e)))?;
+
+        println!("{}", style("✅ Group admins updated!").green());
+        Ok(())
+    }
+
+    /// Change a group's name, description, image or relays (admin only).
+    /// Fields left as `None` in `update` keep their current value.
+    pub async fn update_group_metadata(
+        &mut self,
+        account: &Account,
+        group_id: &GroupId,
+        update: NostrGroupDataUpdate,
+    ) -> Result<GroupData> {
+        if update.name.is_none() && update.description.is_none() && update.image_url.is_none() && update.relays.is_none() {
+            return Err(CliError::InvalidInput(
+                "Nothing to update: pass --name, --description, --image, --clear-image or --relays".to_string(),
+            ).into());
+        }
+        if update.name.as_deref().is_some_and(|name| name.trim().is_empty()) {
+            return Err(CliError::InvalidInput("Group name cannot be empty".to_string()).into());
+        }
+        if update.relays.as_ref().is_some_and(|relays| relays.is_empty()) {
+            return Err(CliError::InvalidInput("A group needs at least one relay".to_string()).into());
+        }
+        if let Some(relays) = &update.relays {
+            NetworkPolicy::current().check_relays(relays.iter().map(|r| r.to_string()))?;
+        }
+        self.ensure_admin(account, group_id).await?;
+
+        println!("{}", style("📝 Updating group details...").yellow());
+        self.update_group_data(account, group_id, update).await
+            .map_err(|e| CliError::Mls(format!("Failed to update group: {}", e)))?;
+        println!("{}", style("✅ Group details updated!").green());
+
+        self.fetch_groups(account).await?
+            .into_iter()
+            .find(|group| group.mls_group_id == Self::group_id_to_string(group_id))
+            .ok_or_else(|| CliError::NotFound("Group not found after update".to_string()).into())
+    }
+
+    async fn update_group_data(&self, account: &Account, group_id: &GroupId, update: NostrGroupDataUpdate) -> Result<()> {
+        let whitenoise = Whitenoise::get_instance()
+            .map_err(|e| anyhow::anyhow!("Failed to get WhiteNoise instance: {:?}", e))?;
+
+        let account_clone = account.clone();
+        let group_id_clone = group_id.clone();
+
+        tokio::task::spawn_blocking(move || {
+            tokio::runtime::Handle::current().block_on(whitenoise.update_group_data(
+                &account_clone,
+                &group_id_clone,
+                update,
+            ))
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("Task join error: {}", e))?
+        .map_err(|e| anyhow::anyhow!("{:?}", e))
+    }
+
+    /// Make a member an admin. Returns false if they already were one.
+    pub async fn promote_admin(&self, account: &Account, group_id: &GroupId, member: PublicKey) -> Result<bool> {
+        let mut admins = self.ensure_admin(account, group_id).await?;
+        if admins.contains(&member) {
+            return Ok(false);
+        }
+
+        let members = self.fetch_group_members(account, group_id).await?;
+        if !members.contains(&member) {
+            return Err(CliError::NotFound(format!("{} is not a member of this group", member.to_hex())).into());
+        }
+
+        admins.push(member);
+        self.update_group_admins(account, group_id, admins).await?;
+        Ok(true)
+    }
+
+    /// Revoke a member's admin rights. Returns false if they weren't an admin.
+    pub async fn demote_admin(&self, account: &Account, group_id: &GroupId, member: PublicKey) -> Result<bool> {
+        let mut admins = self.ensure_admin(account, group_id).await?;
+        if !admins.contains(&member) {
+            return Ok(false);
+        }
+        if admins.len() == 1 {
+            return Err(CliError::InvalidInput("Cannot demote the last admin of a group".to_string()).into());
+        }
+
+        admins.retain(|admin| admin != &member);
+        self.update_group_admins(account, group_id, admins).await?;
+        Ok(true)
+    }
+
+    /// The MLS exporter secret of `epoch` (the current epoch if `None`),
+    /// which attachment keys are derived from
+    pub async fn media_secret(&self, account: &Account, group_id: &GroupId, epoch: Option<u64>) -> Result<MediaSecret> {
+        let whitenoise = Whitenoise::get_instance()
+            .map_err(|e| anyhow::anyhow!("Failed to get WhiteNoise instance: {:?}", e))?;
+
+        let epoch = match epoch {
+            Some(epoch) => epoch,
+            None => whitenoise.fetch_groups(account, true).await
+                .map_err(|e| anyhow::anyhow!("Failed to fetch groups: {:?}", e))?
+                .into_iter()
+                .find(|group| &group.mls_group_id == group_id)
+                .map(|group| group.epoch)
+                .ok_or_else(|| CliError::NotFound(format!("Group {} not found", Self::group_id_to_string(group_id))))?,
+        };
+        let secret = whitenoise.fetch_group_exporter_secret(account, group_id, epoch).await
+            .map_err(|e| CliError::Mls(format!("Failed to get the group secret for epoch {}: {:?}", epoch, e)))?;
+        Ok(MediaSecret { epoch, secret })
+    }
+
+    /// Members, admins and current epoch of a group
+    pub async fn membership(&self, account: &Account, group_id: &GroupId) -> Result<GroupMembership> {
+        let whitenoise = Whitenoise::get_instance()
+            .map_err(|e| anyhow::anyhow!("Failed to get WhiteNoise instance: {:?}", e))?;
+
+        let members = self.fetch_group_members(account, group_id).await?;
+        let admins = self.fetch_group_admins(account, group_id).await?;
+        let epoch = whitenoise.fetch_groups(account, true).await
+            .map_err(|e| anyhow::anyhow!("Failed to fetch groups: {:?}", e))?
+            .into_iter()
+            .find(|group| &group.mls_group_id == group_id)
+            .map(|group| group.epoch);
+
+        Ok(GroupMembership {
+            group_id: Self::group_id_to_string(group_id),
+            members: members.iter().map(|pk| pk.to_hex()).collect(),
+            admins: admins.iter().map(|pk| pk.to_hex()).collect(),
+            epoch,
+        })
+    }
+
+    /// Invitations received by the account, newest first
+    pub async fn fetch_invites(&self, account: &Account, include_handled: bool) -> Result<Vec<InviteData>> {
+        let whitenoise = Whitenoise::get_instance()
+            .map_err(|e| anyhow::anyhow!("Failed to get WhiteNoise instance: {:?}", e))?;
+
+        let mut welcomes = whitenoise.fetch_welcomes(account).await
+            .map_err(|e| anyhow::anyhow!("Failed to fetch invites: {:?}", e))?;
+        welcomes.retain(|w| include_handled || w.state == WelcomeState::Pending);
+
+        let mut invites: Vec<InviteData> = welcomes.iter().map(InviteData::from_welcome).collect();
+        invites.sort_by_key(|invite| std::cmp::Reverse(invite.received_at));
+        Ok(invites)
+    }
+
+    /// Find a pending invitation by id (or unique id prefix)
+    pub async fn find_pending_invite(&self, account: &Account, invite_id: &str) -> Result<InviteData> {
+        let matches: Vec<InviteData> = self.fetch_invites(account, false).await?
+            .into_iter()
+            .filter(|invite| invite.id.starts_with(invite_id))
+            .collect();
+
+        match matches.len() {
+            0 => Err(CliError::NotFound(format!("No pending invite with id {}", invite_id)).into()),
+            1 => Ok(matches.into_iter().next().unwrap()),
+            _ => Err(CliError::InvalidInput(format!("Invite id {} is ambiguous", invite_id)).into()),
+        }
+    }
+
+    pub async fn accept_invite(&self, account: &Account, invite: &InviteData) -> Result<()> {
+        // Joining would subscribe to the group's relays
+        NetworkPolicy::current().check_relays(invite.relays.iter().cloned())?;
+        let whitenoise = Whitenoise::get_instance()
+            .map_err(|e| anyhow::anyhow!("Failed to get WhiteNoise instance: {:?}", e))?;
+
+        let account_clone = account.clone();
+        let invite_id = invite.id.clone();
+
+        tokio::task::spawn_blocking(move || {
+            tokio::runtime::Handle::current().block_on(whitenoise.accept_welcome(&account_clone, invite_id))
+        })
+        .await
+        .map_err(|e| anyhow::anyhow!("Task join error: {}", e))?
+        .map_err(|e| CliError::Mls(format!("Failed to accept invite: {:?}", e)).into())
+    }
+
+    pub async fn decline_invite(&self, account: &Account, invite: &InviteData) -> Result<()> {
+        let whitenoise = Whitenoise::get_instance()
+            .map_err(|e| anyhow::anyhow!("Failed to get WhiteNoise instance: {:?}", e))?;
+
+        whitenoise.decline_welcome(account, invite.id.clone()).await
+            .map_err(|e| CliError::Mls(format!("Failed to decline invite: {:?}", e)).into())
+    }
+
+    /// Compose `message` into an inner event and send it to the group
     pub async fn send_message_to_group(
         &self,
         account: &Account,
         group_id: &GroupId,
-        message: String,
-        kind: u16,
+        message: OutgoingMessage,
     ) -> Result<MessageWithTokens> {
+        if let OutgoingMessage::Deletion { target, .. } = &message {
+            if target.author != account.pubkey {
+                return Err(CliError::PermissionDenied("Only your own messages can be deleted".to_string()).into());
+            }
+        }
+
         let whitenoise = Whitenoise::get_instance()
             .map_err(|e| anyhow::anyhow!("Failed to get WhiteNoise instance: {:?}", e))?;
 
         let account_clone = account.clone();
         let group_id_clone = group_id.clone();
+        let kind = message.kind().as_u16();
+        let tags = message.tags()?;
+        let tags = if tags.is_empty() { None } else { Some(tags) };
+        let content = message.into_content();
 
         let message_with_tokens = tokio::task::spawn_blocking(move || {
             tokio::runtime::Handle::current().block_on(whitenoise.send_message_to_group(
                 &account_clone,
                 &group_id_clone,
-                message,
+                content,
                 kind,
-                None, // tags
+                tags,
             ))
         })
         .await
         .map_err(|e| anyhow::anyhow!("Task join error: {}", e))?
-        .map_err(|e| anyhow::anyhow!("Failed to send message: {:?}", e))?;
+        .map_err(|e| CliError::Mls(format!("Failed to send message: {:?}", e)))?;
 
         Ok(message_with_tokens)
     }
 
+    /// Find a message in a group by id (or unique id prefix)
+    pub async fn find_message(&self, account: &Account, group_id: &GroupId, message_id: &str) -> Result<ChatMessage> {
+        let matches: Vec<ChatMessage> = self.fetch_aggregated_messages_for_group(account, group_id).await?
+            .into_iter()
+            .filter(|m| m.id.starts_with(message_id))
+            .collect();
+
+        match matches.len() {
+            0 => Err(CliError::NotFound(format!("Message not found: {}", message_id)).into()),
+            1 => Ok(matches.into_iter().next().unwrap()),
+            _ => Err(CliError::InvalidInput(format!("Message id {} is ambiguous", message_id)).into()),
+        }
+    }
+
     pub async fn fetch_messages_for_group(
         &self,
         account: &Account,
@@ -269,7 +1000,7 @@ impl GroupManager {
 
     pub fn group_id_from_string(group_id_str: &str) -> Result<GroupId> {
         let bytes = hex::decode(group_id_str)
-            .map_err(|e| anyhow::anyhow!("Failed to decode group ID: {}", e))?;
+            .map_err(|e| CliError::InvalidInput(format!("Failed to decode group ID: {}", e)))?;
         Ok(GroupId::from_slice(&bytes))
     }
 
@@ -336,7 +1067,7 @@ impl GroupManager {
         })
         .await
         .map_err(|e| anyhow::anyhow!("Task join error: {}", e))?
-        .map_err(|e| anyhow::anyhow!("Failed to create DM group: {:?}", e))?;
+        .map_err(|e| CliError::Mls(format!("Failed to create DM group: {:?}", e)))?;
 
         Ok(group.mls_group_id)
     }
@@ -371,4 +1102,137 @@ impl GroupManager {
 
         Ok(None)
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_text_kind_validation() {
+        assert_eq!(MessageKind::for_text(9).unwrap(), MessageKind::Chat);
+        assert_eq!(MessageKind::for_text(1).unwrap(), MessageKind::TextNote);
+        assert!(MessageKind::for_text(7).is_err());
+        assert!(MessageKind::for_text(5).is_err());
+        assert!(MessageKind::for_text(42).is_err());
+        assert_eq!(OutgoingMessage::text("hi").kind().as_u16(), 9);
+    }
+
+    #[test]
+    fn test_cursor_roundtrip() {
+        let cursor = encode_cursor(1700000000, "abc:def");
+        assert_eq!(decode_cursor(&cursor).unwrap(), (1700000000, "abc:def".to_string()));
+        assert!(decode_cursor("not a cursor").is_err());
+    }
+
+    struct Entry {
+        id: String,
+        created_at: u64,
+        author: PublicKey,
+        deleted: bool,
+    }
+
+    impl Entry {
+        fn new(id: &str, created_at: u64, author: &str) -> Self {
+            let author = PublicKey::from_hex(&author.repeat(32)).unwrap();
+            Self { id: id.to_string(), created_at, author, deleted: false }
+        }
+    }
+
+    impl HistoryEntry for Entry {
+        fn entry_id(&self) -> &str {
+            &self.id
+        }
+
+        fn entry_created_at(&self) -> u64 {
+            self.created_at
+        }
+
+        fn entry_author(&self) -> &PublicKey {
+            &self.author
+        }
+
+        fn entry_is_deleted(&self) -> bool {
+            self.deleted
+        }
+    }
+
+    #[test]
+    fn test_count_unread() {
+        let me = PublicKey::from_hex(&"aa".repeat(32)).unwrap();
+        let mut messages = vec![
+            Entry::new("m1", 100, "bb"),
+            Entry::new("m2", 200, "aa"),
+            Entry::new("m3", 200, "bb"),
+            Entry::new("m4", 300, "bb"),
+        ];
+        messages[3].deleted = true;
+
+        // Own and deleted messages never count
+        assert_eq!(count_unread(&messages, &me, None), 2);
+        // The marker is the newest read message, so messages at that second are read
+        assert_eq!(count_unread(&messages, &me, Some(100)), 1);
+        assert_eq!(count_unread(&messages, &me, Some(200)), 0);
+    }
+
+    fn page_ids(page: &PageArgs) -> Result<(Vec<String>, PageInfo)> {
+        // Ten messages at t = 100, 110, ... 190, handed over out of order
+        let history = (0..10u64).rev().map(|i| Entry::new(&format!("m{}", i), 100 + i * 10, "bb")).collect();
+        let (messages, info) = paginate_messages(history, page)?;
+        Ok((messages.into_iter().map(|m| m.id).collect(), info))
+    }
+
+    #[test]
+    fn test_paginate_messages() {
+        let page = |limit| PageArgs { limit, offset: 0, before: None, after: None, since_id: None, cursor: None };
+
+        // Newest page first, in chronological order, with a cursor for the next one
+        let (ids, info) = page_ids(&page(4)).unwrap();
+        assert_eq!(ids, ["m6", "m7", "m8", "m9"]);
+        assert_eq!((info.total, info.has_more), (10, true));
+
+        let (ids, info) = page_ids(&PageArgs { cursor: info.next_cursor, ..page(4) }).unwrap();
+        assert_eq!(ids, ["m2", "m3", "m4", "m5"]);
+        let (ids, info) = page_ids(&PageArgs { cursor: info.next_cursor, ..page(4) }).unwrap();
+        assert_eq!(ids, ["m0", "m1"]);
+        assert_eq!((info.has_more, info.next_cursor), (false, None));
+
+        let (ids, _) = page_ids(&PageArgs { offset: 8, ..page(4) }).unwrap();
+        assert_eq!(ids, ["m0", "m1"]);
+
+        // after is inclusive, before exclusive
+        let (ids, info) = page_ids(&PageArgs { after: Some("130".to_string()), before: Some("160".to_string()), ..page(20) }).unwrap();
+        assert_eq!(ids, ["m3", "m4", "m5"]);
+        assert_eq!((info.total, info.has_more), (3, false));
+
+        let (ids, _) = page_ids(&PageArgs { since_id: Some("m7".to_string()), ..page(20) }).unwrap();
+        assert_eq!(ids, ["m8", "m9"]);
+        assert!(page_ids(&PageArgs { since_id: Some("gone".to_string()), ..page(20) }).is_err());
+    }
+
+    #[test]
+    fn test_group_filter() {
+        let group = |state, archived| GroupData {
+            mls_group_id: "aa".to_string(),
+            nostr_group_id: "bb".to_string(),
+            name: "test".to_string(),
+            description: String::new(),
+            image_url: None,
+            admin_pubkeys: Vec::new(),
+            last_message_id: None,
+            last_message_at: None,
+            group_type: GroupType::Group,
+            epoch: 0,
+            state,
+            archived,
+        };
+
+        let active = group(GroupState::Active, false);
+        let inactive = group(GroupState::Inactive, false);
+        let archived = group(GroupState::Active, true);
+        assert!(active.matches(None) && inactive.matches(None) && !archived.matches(None));
+        assert!(active.matches(Some(GroupFilter::Active)) && !inactive.matches(Some(GroupFilter::Active)));
+        assert!(inactive.matches(Some(GroupFilter::Inactive)) && !archived.matches(Some(GroupFilter::Inactive)));
+        assert!(archived.matches(Some(GroupFilter::Archived)) && !active.matches(Some(GroupFilter::Archived)));
+    }
+}
@@ -0,0 +1,165 @@
+use anyhow::Result;
+use nostr::nips::nip05::{Nip05Address, Nip05Profile};
+use nostr::nips::nip19::{FromBech32, Nip19Profile};
+use std::time::Duration;
+use whitenoise::{PublicKey, RelayUrl};
+
+use crate::contacts::ContactManager;
+use crate::errors::CliError;
+use crate::relays::NetworkPolicy;
+
+const NIP05_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A public key resolved from user input, with any relays the identifier
+/// pointed at (nprofile relay hints or the relays of a NIP-05 document)
+#[derive(Debug, Clone)]
+pub struct Identity {
+    pub pubkey: PublicKey,
+    pub relays: Vec<RelayUrl>,
+}
+
+/// Resolve a hex key, npub, nprofile, `name@domain` NIP-05 identifier or
+/// local contact name to a public key
+pub async fn resolve(input: &str, contacts: &ContactManager) -> Result<Identity> {
+    let input = input.trim();
+    if let Some(identity) = parse_key(input)? {
+        return Ok(identity);
+    }
+    if let Some(identity) = resolve_nip05(input).await? {
+        return Ok(identity);
+    }
+    if let Some(pubkey) = contact_by_name(contacts, input)? {
+        let pubkey = PublicKey::from_hex(&pubkey)
+            .map_err(|e| CliError::InvalidKey(format!("Invalid public key {}: {:?}", pubkey, e)))?;
+        return Ok(Identity { pubkey, relays: Vec::new() });
+    }
+    Err(CliError::InvalidKey(format!(
+        "{} is not a public key, npub, nprofile, NIP-05 identifier or contact name", input
+    )).into())
+}
+
+/// Parse identifiers that carry the key itself: hex, npub or nprofile.
+/// Returns `None` for anything else, and an error for malformed keys.
+pub fn parse_key(input: &str) -> Result<Option<Identity>> {
+    let input = input.trim();
+    let lower = input.to_lowercase();
+    if lower.starts_with("nprofile1") {
+        let profile = Nip19Profile::from_bech32(input)
+            .map_err(|e| CliError::InvalidKey(format!("Invalid nprofile {}: {}", input, e)))?;
+        return Ok(Some(Identity { pubkey: profile.public_key, relays: profile.relays }));
+    }
+    let is_hex = input.len() == 64 && input.chars().all(|c| c.is_ascii_hexdigit());
+    if is_hex || lower.starts_with("npub1") {
+        let pubkey = PublicKey::parse(input)
+            .map_err(|e| CliError::InvalidKey(format!("Invalid public key {}: {}", input, e)))?;
+        return Ok(Some(Identity { pubkey, relays: Vec::new() }));
+    }
+    Ok(None)
+}
+
+/// Resolve a `name@domain` NIP-05 identifier; `None` if `input` is not one.
+/// A bare domain is not treated as NIP-05 so it cannot shadow a contact name.
+pub async fn resolve_nip05(input: &str) -> Result<Option<Identity>> {
+    let input = input.trim();
+    let address = match input.contains('@').then(|| Nip05Address::parse(input).ok()).flatten() {
+        Some(address) => address,
+        None => return Ok(None),
+    };
+    let profile = lookup_nip05(&address).await?;
+    Ok(Some(Identity { pubkey: profile.public_key, relays: profile.relays }))
+}
+
+/// Fetch the address's `/.well-known/nostr.json`. Loopback hosts are
+/// queried over plain http so a local server can stand in.
+async fn lookup_nip05(address: &Nip05Address) -> Result<Nip05Profile> {
+    let identifier = format!("{}@{}", address.name(), address.domain());
+    let mut url = address.url().clone();
+    if matches!(url.host_str(), Some("localhost" | "127.0.0.1" | "[::1]")) {
+        let _ = url.set_scheme("http");
+    }
+    NetworkPolicy::current().check_http(url.as_str())?;
+
+    // NIP-05 forbids following redirects
+    let http = reqwest::Client::builder()
+        .timeout(NIP05_TIMEOUT)
+        .redirect(reqwest::redirect::Policy::none())
+        .build()?;
+    let response = http.get(url).send().await
+        .map_err(|e| CliError::Relay(format!("NIP-05 lookup for {} failed: {}", identifier, e)))?;
+    if !response.status().is_success() {
+        return Err(CliError::NotFound(format!(
+            "NIP-05 lookup for {} failed: {}", identifier, response.status()
+        )).into());
+    }
+    let document = response.text().await
+        .map_err(|e| CliError::Relay(format!("NIP-05 lookup for {} failed: {}", identifier, e)))?;
+    Nip05Profile::from_raw_json(address, &document)
+        .map_err(|e| CliError::NotFound(format!("{} is not listed by {}: {}", identifier, address.domain(), e)).into())
+}
+
+/// Public key of the contact called `name` (case-insensitive)
+fn contact_by_name(contacts: &ContactManager, name: &str) -> Result<Option<String>> {
+    let matches: Vec<_> = contacts.list().into_iter()
+        .filter(|c| c.name.eq_ignore_ascii_case(name))
+        .collect();
+    match matches.as_slice() {
+        [] => Ok(None),
+        [contact] => Ok(Some(contact.public_key.clone())),
+        _ => Err(CliError::InvalidInput(format!(
+            "Several contacts are named {}; use their npub instead", name
+        )).into()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    const PUBKEY: &str = "3bf0c63fcb93463407af97a5e5ee64fa883d107ef9e558472c4eb9aaaefa459d";
+
+    #[test]
+    fn test_parse_npub_and_nprofile() {
+        let npub = "npub180cvv07tjdrrgpa0j7j7tmnyl2yr6yr7l8j4s3evf6u64th6gkwsyjh6w6";
+        assert_eq!(parse_key(npub).unwrap().unwrap().pubkey.to_hex(), PUBKEY);
+        assert_eq!(parse_key(PUBKEY).unwrap().unwrap().pubkey.to_hex(), PUBKEY);
+        assert!(parse_key(&npub.replace("w6", "w7")).is_err());
+        assert!(parse_key("bob").unwrap().is_none());
+
+        let nprofile = "nprofile1qqsrhuxx8l9ex335q7he0f09aej04zpazpl0ne2cgukyawd24mayt8gpp4mhxue69uhhytnc9e3k7mgpz4mhxue69uhkg6nzv9ejuumpv34kytnrdaksjlyr9p";
+        let identity = parse_key(nprofile).unwrap().unwrap();
+        assert_eq!(identity.pubkey.to_hex(), PUBKEY);
+        assert_eq!(identity.relays.iter().map(|r| r.to_string()).collect::<Vec<_>>(), ["wss://r.x.com", "wss://djbas.sadkb.com"]);
+    }
+
+    #[tokio::test]
+    async fn test_nip05_lookup_against_local_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let domain = listener.local_addr().unwrap().to_string();
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut stream, _)) = listener.accept().await else { return };
+                let mut buf = [0u8; 4096];
+                let n = stream.read(&mut buf).await.unwrap();
+                let request = String::from_utf8_lossy(&buf[..n]).to_string();
+                let body = if request.starts_with("GET /.well-known/nostr.json?name=bob ") {
+                    format!(r#"{{"names":{{"bob":"{}"}},"relays":{{"{}":["wss://relay.example.com"]}}}}"#, PUBKEY, PUBKEY)
+                } else {
+                    r#"{"names":{}}"#.to_string()
+                };
+                let response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(), body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        let identity = resolve_nip05(&format!("bob@{}", domain)).await.unwrap().unwrap();
+        assert_eq!(identity.pubkey.to_hex(), PUBKEY);
+        assert_eq!(identity.relays.iter().map(|r| r.to_string()).collect::<Vec<_>>(), ["wss://relay.example.com"]);
+        assert!(resolve_nip05(&format!("alice@{}", domain)).await.is_err());
+        assert!(resolve_nip05(&domain).await.unwrap().is_none());
+    }
+}
@@ -1,136 +1,319 @@
 use anyhow::Result;
 use base64::{Engine as _, engine::general_purpose};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use rand::RngCore;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
+use std::io::{BufRead, IsTerminal};
 use std::path::PathBuf;
 
+use crate::errors::CliError;
+use crate::storage::write_file;
+
+/// Current on-disk format version. Version 1 files held XOR-obfuscated keys
+/// and are re-encrypted the first time a passphrase is supplied.
+const STORE_VERSION: u32 = 2;
+
+/// Environment variable consulted for the key store passphrase
+pub const PASSPHRASE_ENV: &str = "WHITENOISE_KEYSTORE_PASSPHRASE";
+
+/// scrypt cost parameters for newly encrypted keys
+const DEFAULT_LOG_N: u8 = 16;
+const SCRYPT_R: u32 = 8;
+const SCRYPT_P: u32 = 1;
+
 #[derive(Debug, Serialize, Deserialize)]
 struct FileKeyStore {
+    version: u32,
+    keys: HashMap<String, EncryptedKey>,
+}
+
+/// Version 1 store, kept only so existing files can be migrated
+#[derive(Debug, Serialize, Deserialize)]
+struct LegacyKeyStore {
     version: u32,
     keys: HashMap<String, String>,
 }
 
+#[derive(Debug, Deserialize)]
+struct StoreHeader {
+    version: u32,
+}
+
+/// A private key sealed with XChaCha20-Poly1305 under a scrypt-derived key.
+/// The public key is bound as associated data so entries cannot be swapped.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct EncryptedKey {
+    kdf: String,
+    log_n: u8,
+    r: u32,
+    p: u32,
+    salt: String,
+    nonce: String,
+    ciphertext: String,
+}
+
+enum LoadedStore {
+    Current(FileKeyStore),
+    Legacy(LegacyKeyStore),
+}
+
 pub struct KeyringHelper {
     store_path: PathBuf,
+    log_n: u8,
 }
 
 impl KeyringHelper {
     pub fn new() -> Result<Self> {
         let home = dirs::home_dir().ok_or_else(|| anyhow::anyhow!("No home directory found"))?;
         let store_path = home.join(".whitenoise_keys.json");
-        
-        Ok(Self { store_path })
-    }
-    
-    pub fn store_key(&self, pubkey: &str, privkey: &str) -> Result<()> {
-        let mut store = self.load_store()?;
-        
-        // Simple obfuscation - not secure but matches WhiteNoise approach
-        let obfuscated = self.obfuscate(privkey);
-        store.keys.insert(pubkey.to_string(), obfuscated);
-        
-        self.save_store(&store)?;
-        Ok(())
+
+        Ok(Self::with_path(store_path))
     }
-    
-    pub fn get_key(&self, pubkey: &str) -> Result<Option<String>> {
-        let store = self.load_store()?;
-        
-        if let Some(obfuscated) = store.keys.get(pubkey) {
-            let privkey = self.deobfuscate(obfuscated)?;
+
+    pub fn with_path(store_path: PathBuf) -> Self {
+        Self {
+            store_path,
+            log_n: DEFAULT_LOG_N,
+        }
+    }
+
+    pub fn store_key(&self, pubkey: &str, privkey: &str, passphrase: &str) -> Result<()> {
+        let mut store = self.load_current_store(passphrase)?;
+        self.verify_passphrase(&store, passphrase)?;
+
+        let sealed = self.encrypt(pubkey, privkey, passphrase)?;
+        store.keys.insert(pubkey.to_string(), sealed);
+
+        self.write_store(&store)
+    }
+
+    pub fn get_key(&self, pubkey: &str, passphrase: &str) -> Result<Option<String>> {
+        let store = self.load_current_store(passphrase)?;
+
+        if let Some(sealed) = store.keys.get(pubkey) {
+            let privkey = self.decrypt(pubkey, sealed, passphrase)?;
             Ok(Some(privkey))
         } else {
             Ok(None)
         }
     }
-    
+
     pub fn list_keys(&self) -> Result<Vec<String>> {
-        let store = self.load_store()?;
-        Ok(store.keys.keys().cloned().collect())
+        match self.load_store()? {
+            LoadedStore::Current(store) => Ok(store.keys.into_keys().collect()),
+            LoadedStore::Legacy(store) => Ok(store.keys.into_keys().collect()),
+        }
     }
-    
+
     pub fn remove_key(&self, pubkey: &str) -> Result<()> {
-        let mut store = self.load_store()?;
-        store.keys.remove(pubkey);
-        self.save_store(&store)?;
-        Ok(())
+        // Removal needs no passphrase, so it cannot migrate a legacy store and
+        // will not write the obfuscated format again either
+        match self.load_store()? {
+            LoadedStore::Current(mut store) => {
+                store.keys.remove(pubkey);
+                self.write_store(&store)
+            }
+            LoadedStore::Legacy(_) => Err(CliError::KeyStore(
+                "The key store still uses the old obfuscated format; run `keys get` with a passphrase once to re-encrypt it, then remove the key".to_string(),
+            ).into()),
+        }
     }
-    
-    fn load_store(&self) -> Result<FileKeyStore> {
-        if self.store_path.exists() {
-            let content = fs::read_to_string(&self.store_path)?;
-            let store: FileKeyStore = serde_json::from_str(&content)?;
-            Ok(store)
-        } else {
-            Ok(FileKeyStore {
-                version: 1,
+
+    /// Whether the store on disk still uses the obfuscated version 1 format
+    pub fn is_legacy(&self) -> Result<bool> {
+        Ok(matches!(self.load_store()?, LoadedStore::Legacy(_)))
+    }
+
+    fn load_store(&self) -> Result<LoadedStore> {
+        if !self.store_path.exists() {
+            return Ok(LoadedStore::Current(FileKeyStore {
+                version: STORE_VERSION,
                 keys: HashMap::new(),
-            })
+            }));
+        }
+
+        let content = fs::read_to_string(&self.store_path)?;
+        let header: StoreHeader = serde_json::from_str(&content)?;
+        match header.version {
+            1 => Ok(LoadedStore::Legacy(serde_json::from_str(&content)?)),
+            STORE_VERSION => Ok(LoadedStore::Current(serde_json::from_str(&content)?)),
+            other => Err(anyhow::anyhow!("Unsupported key store version: {}", other)),
         }
     }
-    
-    fn save_store(&self, store: &FileKeyStore) -> Result<()> {
-        let content = serde_json::to_string_pretty(store)?;
-        fs::write(&self.store_path, content)?;
-        
-        // Set file permissions to 0600 (read/write for owner only)
-        #[cfg(unix)]
-        {
-            use std::os::unix::fs::PermissionsExt;
-            let metadata = fs::metadata(&self.store_path)?;
-            let mut perms = metadata.permissions();
-            perms.set_mode(0o600);
-            fs::set_permissions(&self.store_path, perms)?;
+
+    /// Load the store, migrating a version 1 file to the encrypted format first
+    fn load_current_store(&self, passphrase: &str) -> Result<FileKeyStore> {
+        match self.load_store()? {
+            LoadedStore::Current(store) => Ok(store),
+            LoadedStore::Legacy(legacy) => {
+                let mut store = FileKeyStore {
+                    version: STORE_VERSION,
+                    keys: HashMap::new(),
+                };
+                for (pubkey, obfuscated) in &legacy.keys {
+                    let privkey = self.deobfuscate(obfuscated)?;
+                    store.keys.insert(pubkey.clone(), self.encrypt(pubkey, &privkey, passphrase)?);
+                }
+                self.write_store(&store)?;
+                Ok(store)
+            }
+        }
+    }
+
+    /// Reject a passphrase that cannot open the keys already in the store
+    fn verify_passphrase(&self, store: &FileKeyStore, passphrase: &str) -> Result<()> {
+        if let Some((pubkey, sealed)) = store.keys.iter().next() {
+            self.decrypt(pubkey, sealed, passphrase)
+                .map_err(|_| CliError::KeyStore("Passphrase does not match the existing key store".to_string()))?;
         }
-        
         Ok(())
     }
-    
+
+    fn write_store<T: Serialize>(&self, store: &T) -> Result<()> {
+        let content = serde_json::to_string_pretty(store)?;
+        // Owner-only from creation, and renamed into place so a crash never leaves a truncated file
+        write_file(&self.store_path, content)
+    }
+
+    fn encrypt(&self, pubkey: &str, privkey: &str, passphrase: &str) -> Result<EncryptedKey> {
+        let mut salt = [0u8; 16];
+        let mut nonce = [0u8; 24];
+        rand::thread_rng().fill_bytes(&mut salt);
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let key = derive_key(passphrase, &salt, self.log_n, SCRYPT_R, SCRYPT_P)?;
+        let cipher = XChaCha20Poly1305::new(&key.into());
+        let ciphertext = cipher
+            .encrypt(XNonce::from_slice(&nonce), Payload { msg: privkey.as_bytes(), aad: pubkey.as_bytes() })
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt private key"))?;
+
+        Ok(EncryptedKey {
+            kdf: "scrypt".to_string(),
+            log_n: self.log_n,
+            r: SCRYPT_R,
+            p: SCRYPT_P,
+            salt: general_purpose::STANDARD.encode(salt),
+            nonce: general_purpose::STANDARD.encode(nonce),
+            ciphertext: general_purpose::STANDARD.encode(ciphertext),
+        })
+    }
+
+    fn decrypt(&self, pubkey: &str, sealed: &EncryptedKey, passphrase: &str) -> Result<String> {
+        if sealed.kdf != "scrypt" {
+            return Err(anyhow::anyhow!("Unsupported key derivation function: {}", sealed.kdf));
+        }
+
+        let salt = general_purpose::STANDARD.decode(&sealed.salt)?;
+        let nonce = general_purpose::STANDARD.decode(&sealed.nonce)?;
+        let ciphertext = general_purpose::STANDARD.decode(&sealed.ciphertext)?;
+        if nonce.len() != 24 {
+            return Err(anyhow::anyhow!("Corrupt key store entry for {}", pubkey));
+        }
+
+        let key = derive_key(passphrase, &salt, sealed.log_n, sealed.r, sealed.p)?;
+        let cipher = XChaCha20Poly1305::new(&key.into());
+        let plaintext = cipher
+            .decrypt(XNonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: pubkey.as_bytes() })
+            .map_err(|_| CliError::KeyStore(format!("Wrong passphrase or corrupt key store entry for {}", pubkey)))?;
+
+        Ok(String::from_utf8(plaintext)?)
+    }
+
+    #[cfg(test)]
     fn obfuscate(&self, data: &str) -> String {
         // Simple XOR obfuscation with a fixed key
         let key = b"WhiteNoiseCLI2024";
         let data_bytes = data.as_bytes();
         let mut obfuscated = Vec::with_capacity(data_bytes.len());
-        
+
         for (i, &byte) in data_bytes.iter().enumerate() {
             obfuscated.push(byte ^ key[i % key.len()]);
         }
-        
+
         general_purpose::STANDARD.encode(&obfuscated)
     }
-    
+
     fn deobfuscate(&self, obfuscated: &str) -> Result<String> {
         let key = b"WhiteNoiseCLI2024";
         let data = general_purpose::STANDARD.decode(obfuscated)?;
         let mut deobfuscated = Vec::with_capacity(data.len());
-        
+
         for (i, &byte) in data.iter().enumerate() {
             deobfuscated.push(byte ^ key[i % key.len()]);
         }
-        
+
         Ok(String::from_utf8(deobfuscated)?)
     }
 }
 
+fn derive_key(passphrase: &str, salt: &[u8], log_n: u8, r: u32, p: u32) -> Result<[u8; 32]> {
+    let params = scrypt::Params::new(log_n, r, p, 32)
+        .map_err(|e| anyhow::anyhow!("Invalid scrypt parameters: {}", e))?;
+    let mut key = [0u8; 32];
+    scrypt::scrypt(passphrase.as_bytes(), salt, &params, &mut key)
+        .map_err(|e| anyhow::anyhow!("Key derivation failed: {}", e))?;
+    Ok(key)
+}
+
+/// Resolve the key store passphrase from stdin (when requested), the
+/// environment, or an interactive prompt, in that order.
+pub fn read_passphrase(from_stdin: bool, confirm: bool) -> Result<String> {
+    let passphrase = if from_stdin {
+        let mut line = String::new();
+        std::io::stdin().lock().read_line(&mut line)?;
+        line.trim_end_matches(['\r', '\n']).to_string()
+    } else if let Ok(passphrase) = std::env::var(PASSPHRASE_ENV) {
+        passphrase
+    } else if std::io::stdin().is_terminal() {
+        let mut prompt = dialoguer::Password::new().with_prompt("Key store passphrase");
+        if confirm {
+            prompt = prompt.with_confirmation("Confirm passphrase", "Passphrases do not match");
+        }
+        prompt.interact()?
+    } else {
+        return Err(anyhow::anyhow!(
+            "No passphrase available: set {}, use --passphrase-stdin, or run in a terminal",
+            PASSPHRASE_ENV
+        ));
+    };
+
+    if passphrase.is_empty() {
+        return Err(CliError::KeyStore("Passphrase must not be empty".to_string()).into());
+    }
+    Ok(passphrase)
+}
+
 // Environment setup for keyring-less operation
 pub fn setup_keyring_environment() -> Result<()> {
     // Set environment variables to use file storage instead of keyring
     std::env::set_var("WHITENOISE_FILE_STORAGE", "1");
     std::env::set_var("WHITENOISE_NO_KEYRING", "1");
-    
+
     // Create dummy D-Bus session for environments without it
     if std::env::var("DBUS_SESSION_BUS_ADDRESS").is_err() {
         std::env::set_var("DBUS_SESSION_BUS_ADDRESS", "disabled:");
     }
-    
+
     Ok(())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    
+
+    fn temp_helper() -> KeyringHelper {
+        let path = std::env::temp_dir().join(format!("whitenoise_keys_{}.json", uuid::Uuid::new_v4()));
+        let mut helper = KeyringHelper::with_path(path);
+        // Keep scrypt cheap so the tests stay fast
+        helper.log_n = 4;
+        helper
+    }
+
     #[test]
     fn test_obfuscation() {
         let helper = KeyringHelper::new().unwrap();
@@ -139,4 +322,36 @@ mod tests {
         let deobfuscated = helper.deobfuscate(&obfuscated).unwrap();
         assert_eq!(original, deobfuscated);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_encrypted_roundtrip() {
+        let helper = temp_helper();
+        helper.store_key("pk1", "nsec1secret", "correct horse").unwrap();
+
+        let content = fs::read_to_string(&helper.store_path).unwrap();
+        assert!(!content.contains("nsec1secret"));
+        assert_eq!(helper.get_key("pk1", "correct horse").unwrap().as_deref(), Some("nsec1secret"));
+        assert!(helper.get_key("pk1", "wrong").is_err());
+        assert!(helper.store_key("pk2", "nsec1other", "wrong").is_err());
+
+        fs::remove_file(&helper.store_path).unwrap();
+    }
+
+    #[test]
+    fn test_legacy_migration() {
+        let helper = temp_helper();
+        let legacy = LegacyKeyStore {
+            version: 1,
+            keys: HashMap::from([("pk1".to_string(), helper.obfuscate("nsec1legacy"))]),
+        };
+        helper.write_store(&legacy).unwrap();
+        assert!(helper.is_legacy().unwrap());
+        assert!(helper.remove_key("pk1").is_err());
+
+        assert_eq!(helper.get_key("pk1", "passphrase").unwrap().as_deref(), Some("nsec1legacy"));
+        assert!(!helper.is_legacy().unwrap());
+        assert_eq!(helper.list_keys().unwrap(), vec!["pk1".to_string()]);
+
+        fs::remove_file(&helper.store_path).unwrap();
+    }
+}
@@ -3,6 +3,7 @@ use clap::Parser;
 use console::{style, Term};
 
 mod app;
+mod chat;
 mod account;
 mod contacts;
 mod groups;
@@ -13,11 +14,28 @@ mod whitenoise_config;
 mod cli;
 mod cli_handler;
 mod keyring_helper;
+mod config;
+mod errors;
+mod output;
+mod media;
+mod search;
+mod export;
+mod identifiers;
+#[cfg(test)]
+mod test_relay;
 
 use app::App;
 use whitenoise_config::WhitenoiseManager;
-use cli::Cli;
+use cli::{Cli, OutputFormat};
 use cli_handler::CliHandler;
+use config::Config;
+use errors::CliError;
+use relays::NetworkPolicy;
+
+/// Default tracing filter for interactive mode. These are internal library
+/// issues that don't affect CLI functionality.
+const INTERACTIVE_LOG_FILTER: &str =
+    "error,whitenoise::event_processor=off,nostr_relay_pool::relay::inner=off,whitenoise::delete_key_package_from_relays_for_account=off,nostr_relay_pool::pool=off,nostr_relay_pool=off";
 
 #[tokio::main]
 async fn main() -> Result<()> {
@@ -25,19 +43,35 @@ async fn main() -> Result<()> {
     
     // Check if we should run in CLI mode (non-interactive)
     if cli.command.is_some() {
-        // CLI mode - handle commands and exit
-        run_cli_mode(cli).await
-    } else if cli.interactive {
+        // CLI mode - handle commands and exit. Command failures are reported by
+        // the handler; this catches startup failures before it exists.
+        if let Err(e) = run_cli_mode(cli).await {
+            let error = CliError::from(e);
+            eprintln!("{} {} {}", style("Error:").red().bold(), error, style(format!("({})", error.code())).dim());
+            std::process::exit(error.exit_code());
+        }
+        return Ok(());
+    }
+
+    let config = Config::load(cli.config.as_deref())?;
+    NetworkPolicy::from_flags(cli.offline, cli.relays.as_deref())?.install()?;
+    if cli.interactive {
         // Explicitly requested interactive mode
-        run_interactive_mode().await
+        run_interactive_mode(config, cli.relay_profile).await
     } else {
         // Default to interactive mode when no command specified
-        run_interactive_mode().await
+        run_interactive_mode(config, cli.relay_profile).await
     }
 }
 
 async fn run_cli_mode(cli: Cli) -> Result<()> {
-    let mut handler = CliHandler::new(cli.output, cli.quiet, cli.account).await?;
+    let config = Config::load(cli.config.as_deref()).map_err(|e| CliError::Config(e.to_string()))?;
+    NetworkPolicy::from_flags(cli.offline, cli.relays.as_deref())?.install()?;
+
+    // Command-line flags take precedence over the config file
+    let output = cli.output.or_else(|| config.output.clone()).unwrap_or(OutputFormat::Human);
+    let account = cli.account.or_else(|| config.default_account.clone());
+    let mut handler = CliHandler::new(config, output, cli.quiet, account, cli.relay_profile).await?;
     
     if let Some(command) = cli.command {
         handler.handle_command(command).await?;
@@ -46,12 +80,9 @@ async fn run_cli_mode(cli: Cli) -> Result<()> {
     Ok(())
 }
 
-async fn run_interactive_mode() -> Result<()> {
+async fn run_interactive_mode(config: Config, relay_profile: Option<String>) -> Result<()> {
     // Configure selective logging to filter out known library issues
-    // These are internal library issues that don't affect CLI functionality
-    std::env::set_var("RUST_LOG", 
-        "error,whitenoise::event_processor=off,nostr_relay_pool::relay::inner=off,whitenoise::delete_key_package_from_relays_for_account=off,nostr_relay_pool::pool=off,nostr_relay_pool=off"
-    );
+    std::env::set_var("RUST_LOG", config.log_filter_or(INTERACTIVE_LOG_FILTER));
     
     let term = Term::stdout();
     term.clear_screen()?;
@@ -61,13 +92,23 @@ async fn run_interactive_mode() -> Result<()> {
     println!();
 
     // Initialize WhiteNoise
-    let mut whitenoise_manager = WhitenoiseManager::new()?;
+    let mut whitenoise_manager = WhitenoiseManager::new(&config)?;
     println!("{}", style("🔧 Initializing WhiteNoise...").yellow());
     whitenoise_manager.initialize().await?;
     println!("{}", style("✅ WhiteNoise initialized successfully!").green());
     println!();
 
-    let mut app = App::new(whitenoise_manager).await?;
+    let mut app = App::new(whitenoise_manager, config).await?;
+    app.relay_profile = relay_profile;
+    
+    // Fall back to the configured default account when nothing is logged in
+    if !app.account_manager.is_logged_in() {
+        if let Some(pubkey) = app.config.default_account.clone() {
+            if let Err(e) = app.auto_login_by_pubkey(&pubkey).await {
+                eprintln!("{} {}", style("Could not log in default account:").yellow(), e);
+            }
+        }
+    }
     
     loop {
         match app.run_main_menu().await {
@@ -0,0 +1,414 @@
+use anyhow::Result;
+use base64::{engine::general_purpose, Engine as _};
+use chacha20poly1305::{
+    aead::{Aead, KeyInit, Payload},
+    XChaCha20Poly1305, XNonce,
+};
+use hkdf::Hkdf;
+use nostr::secp256k1::Message;
+use nostr::Keys;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::Path;
+use std::time::Duration;
+use whitenoise::Tag;
+
+use crate::errors::CliError;
+use crate::relays::NetworkPolicy;
+
+/// Blossom server used when neither `--server` nor `media_server` is set
+pub const DEFAULT_MEDIA_SERVER: &str = "https://blossom.primal.net";
+
+const KEY_DOMAIN: &[u8] = b"whitenoise-cli/media/v1";
+const ENCRYPTION: &str = "xchacha20poly1305";
+const HTTP_TIMEOUT: Duration = Duration::from_secs(60);
+/// BUD-02 authorization event kind and lifetime
+const BLOSSOM_AUTH_KIND: u16 = 24242;
+const BLOSSOM_AUTH_TTL: u64 = 300;
+
+/// The MLS exporter secret of one group epoch. Only members of the group
+/// at that epoch can compute it, and file keys are derived from it.
+#[derive(Clone)]
+pub struct MediaSecret {
+    pub epoch: u64,
+    pub secret: [u8; 32],
+}
+
+/// An encrypted attachment as described by a message's NIP-92 `imeta` tag.
+/// `sha256` addresses the encrypted blob on the Blossom server;
+/// `original_sha256` is checked after decryption. `epoch` names the group
+/// epoch whose exporter secret the file key was derived from.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MediaAttachment {
+    pub url: String,
+    pub mime_type: String,
+    pub sha256: String,
+    pub original_sha256: String,
+    pub size: u64,
+    pub name: String,
+    pub epoch: u64,
+    pub nonce: String,
+}
+
+impl MediaAttachment {
+    /// `imeta` tag entries, each a space separated `key value` pair
+    pub fn to_imeta(&self) -> Vec<String> {
+        vec![
+            "imeta".to_string(),
+            format!("url {}", self.url),
+            format!("m {}", self.mime_type),
+            format!("x {}", self.sha256),
+            format!("ox {}", self.original_sha256),
+            format!("size {}", self.size),
+            format!("name {}", self.name),
+            format!("encryption {}", ENCRYPTION),
+            format!("epoch {}", self.epoch),
+            format!("nonce {}", self.nonce),
+        ]
+    }
+
+    pub fn to_tag(&self) -> Result<Tag> {
+        Tag::parse(self.to_imeta()).map_err(|e| anyhow::anyhow!("Failed to build imeta tag: {:?}", e))
+    }
+
+    /// Parse an `imeta` tag written by `to_imeta`; other tags yield `None`
+    pub fn from_imeta(parts: &[String]) -> Option<Self> {
+        if parts.first().map(String::as_str) != Some("imeta") {
+            return None;
+        }
+
+        let field = |key: &str| {
+            parts[1..].iter().find_map(|entry| {
+                entry.split_once(' ').filter(|(k, _)| *k == key).map(|(_, v)| v.to_string())
+            })
+        };
+
+        if field("encryption").as_deref() != Some(ENCRYPTION) {
+            return None;
+        }
+
+        Some(Self {
+            url: field("url")?,
+            mime_type: field("m").unwrap_or_else(|| "application/octet-stream".to_string()),
+            sha256: field("x")?,
+            original_sha256: field("ox")?,
+            size: field("size").and_then(|s| s.parse().ok()).unwrap_or(0),
+            name: field("name").unwrap_or_else(|| "attachment".to_string()),
+            epoch: field("epoch")?.parse().ok()?,
+            nonce: field("nonce")?,
+        })
+    }
+
+    pub fn from_tags(tags: &[Tag]) -> Vec<Self> {
+        tags.iter().filter_map(|tag| Self::from_imeta(tag.as_slice())).collect()
+    }
+
+    /// Authenticated along with the ciphertext, so a tampered name, type or
+    /// size fails decryption
+    fn associated_data(name: &str, mime_type: &str, size: u64) -> Vec<u8> {
+        serde_json::to_vec(&(name, mime_type, size)).unwrap_or_default()
+    }
+
+    /// Expand the epoch's exporter secret into this file's key, bound to the
+    /// file's hash and metadata as in MIP-04
+    fn file_key(secret: &MediaSecret, original_sha256: &str, name: &str, mime_type: &str) -> Result<[u8; 32]> {
+        let hkdf = Hkdf::<Sha256>::from_prk(&secret.secret)
+            .map_err(|_| anyhow::anyhow!("Invalid group exporter secret"))?;
+        let info = [KEY_DOMAIN, &serde_json::to_vec(&(original_sha256, mime_type, name))?].concat();
+        let mut key = [0u8; 32];
+        hkdf.expand(&info, &mut key)
+            .map_err(|_| anyhow::anyhow!("Failed to derive the file key"))?;
+        Ok(key)
+    }
+}
+
+/// Blob descriptor returned by a Blossom `PUT /upload`
+#[derive(Debug, Clone, Deserialize)]
+pub struct BlobDescriptor {
+    pub url: String,
+    pub sha256: String,
+}
+
+/// Encrypts attachments for a group and moves them to and from a Blossom server.
+///
+/// Each file is sealed with XChaCha20-Poly1305 under a key derived from the
+/// group's MLS exporter secret for the current epoch, so the `imeta` tag
+/// only carries the epoch and nonce. Uploads are authorized with a BUD-02
+/// event signed by the sending account.
+pub struct MediaManager {
+    server: String,
+    http: reqwest::Client,
+}
+
+impl MediaManager {
+    pub fn new(server: &str) -> Result<Self> {
+        NetworkPolicy::current().check_http(server)?;
+        let http = reqwest::Client::builder().timeout(HTTP_TIMEOUT).build()?;
+        Ok(Self {
+            server: server.trim_end_matches('/').to_string(),
+            http,
+        })
+    }
+
+    /// Encrypt and upload a file, returning the attachment to send in the group
+    pub async fn upload_file(&self, secret: &MediaSecret, signer: &Keys, path: &Path) -> Result<MediaAttachment> {
+        let plaintext = std::fs::read(path)
+            .map_err(|e| CliError::InvalidInput(format!("Failed to read {}: {}", path.display(), e)))?;
+        let name = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "attachment".to_string());
+        let mime_type = mime_type_for(path);
+        let size = plaintext.len() as u64;
+        let original_sha256 = hex::encode(Sha256::digest(&plaintext));
+
+        let mut nonce = [0u8; 24];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let key = MediaAttachment::file_key(secret, &original_sha256, &name, mime_type)?;
+        let aad = MediaAttachment::associated_data(&name, mime_type, size);
+        let cipher = XChaCha20Poly1305::new(&key.into());
+        let ciphertext = cipher
+            .encrypt(XNonce::from_slice(&nonce), Payload { msg: &plaintext, aad: &aad })
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt {}", name))?;
+        let sha256 = hex::encode(Sha256::digest(&ciphertext));
+
+        let descriptor = self.upload(ciphertext, &sha256, signer).await?;
+        if descriptor.sha256 != sha256 {
+            return Err(CliError::Relay(format!(
+                "Media server stored a different blob ({} instead of {})",
+                descriptor.sha256, sha256
+            ))
+            .into());
+        }
+
+        Ok(MediaAttachment {
+            url: descriptor.url,
+            mime_type: mime_type.to_string(),
+            sha256,
+            original_sha256,
+            size,
+            name,
+            epoch: secret.epoch,
+            nonce: hex::encode(nonce),
+        })
+    }
+
+    /// Download, verify and decrypt an attachment with the exporter secret of its epoch
+    pub async fn download(&self, secret: &MediaSecret, attachment: &MediaAttachment) -> Result<Vec<u8>> {
+        NetworkPolicy::current().check_http(&attachment.url)?;
+        let response = self.http.get(&attachment.url).send().await
+            .map_err(|e| CliError::Relay(format!("Failed to download {}: {}", attachment.url, e)))?;
+        if !response.status().is_success() {
+            return Err(CliError::Relay(format!("Media server returned {} for {}", response.status(), attachment.url)).into());
+        }
+        let ciphertext = response.bytes().await?;
+
+        if hex::encode(Sha256::digest(&ciphertext)) != attachment.sha256 {
+            return Err(CliError::Relay(format!("Downloaded blob does not match hash {}", attachment.sha256)).into());
+        }
+
+        let nonce = hex::decode(&attachment.nonce)?;
+        if nonce.len() != 24 {
+            return Err(CliError::InvalidInput("Attachment has an invalid nonce".to_string()).into());
+        }
+
+        let key = MediaAttachment::file_key(secret, &attachment.original_sha256, &attachment.name, &attachment.mime_type)?;
+        let aad = MediaAttachment::associated_data(&attachment.name, &attachment.mime_type, attachment.size);
+        let cipher = XChaCha20Poly1305::new(&key.into());
+        let plaintext = cipher
+            .decrypt(XNonce::from_slice(&nonce), Payload { msg: &ciphertext, aad: &aad })
+            .map_err(|_| CliError::Mls(format!("Failed to decrypt {}: not encrypted for this group epoch, or altered", attachment.name)))?;
+
+        if hex::encode(Sha256::digest(&plaintext)) != attachment.original_sha256 {
+            return Err(CliError::Mls(format!("Decrypted {} does not match its hash", attachment.name)).into());
+        }
+        Ok(plaintext)
+    }
+
+    async fn upload(&self, blob: Vec<u8>, sha256: &str, signer: &Keys) -> Result<BlobDescriptor> {
+        let response = self.http
+            .put(format!("{}/upload", self.server))
+            .header(reqwest::header::CONTENT_TYPE, "application/octet-stream")
+            .header(reqwest::header::AUTHORIZATION, upload_authorization(signer, sha256)?)
+            .body(blob)
+            .send()
+            .await
+            .map_err(|e| CliError::Relay(format!("Failed to upload to {}: {}", self.server, e)))?;
+
+        if !response.status().is_success() {
+            return Err(CliError::Relay(format!("Media server {} rejected upload: {}", self.server, response.status())).into());
+        }
+        response.json().await
+            .map_err(|e| CliError::Relay(format!("Invalid upload response from {}: {}", self.server, e)).into())
+    }
+}
+
+/// BUD-02 `Authorization` header value: a signed kind 24242 event allowing
+/// the upload of the blob with hash `sha256` for the next few minutes
+fn upload_authorization(signer: &Keys, sha256: &str) -> Result<String> {
+    let pubkey = signer.public_key().to_hex();
+    let created_at = chrono::Utc::now().timestamp() as u64;
+    let tags = serde_json::json!([
+        ["t", "upload"],
+        ["x", sha256],
+        ["expiration", (created_at + BLOSSOM_AUTH_TTL).to_string()],
+    ]);
+    let content = "Upload encrypted attachment";
+
+    // NIP-01 event id: hash of the canonical serialization
+    let id: [u8; 32] = Sha256::digest(serde_json::to_vec(&serde_json::json!([
+        0, pubkey, created_at, BLOSSOM_AUTH_KIND, tags, content
+    ]))?).into();
+    let sig = signer.sign_schnorr(&Message::from_digest(id));
+
+    let event = serde_json::json!({
+        "id": hex::encode(id),
+        "pubkey": pubkey,
+        "created_at": created_at,
+        "kind": BLOSSOM_AUTH_KIND,
+        "tags": tags,
+        "content": content,
+        "sig": sig.to_string(),
+    });
+    Ok(format!("Nostr {}", general_purpose::STANDARD.encode(event.to_string())))
+}
+
+pub fn mime_type_for(path: &Path) -> &'static str {
+    let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase());
+    match extension.as_deref() {
+        Some("png") => "image/png",
+        Some("jpg") | Some("jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("mp4") => "video/mp4",
+        Some("mov") => "video/quicktime",
+        Some("mp3") => "audio/mpeg",
+        Some("pdf") => "application/pdf",
+        Some("txt") => "text/plain",
+        _ => "application/octet-stream",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Whether `head` carries a valid BUD-02 upload authorization for `body`
+    fn authorized(head: &str, body: &[u8]) -> bool {
+        let Some(token) = head.lines().find_map(|l| {
+            l.split_once(':').filter(|(k, _)| k.eq_ignore_ascii_case("authorization")).map(|(_, v)| v.trim().to_string())
+        }) else { return false };
+        let Some(event) = token.strip_prefix("Nostr ")
+            .and_then(|b64| general_purpose::STANDARD.decode(b64).ok())
+            .and_then(|json| serde_json::from_slice::<serde_json::Value>(&json).ok()) else { return false };
+
+        let tag = |name: &str| event["tags"].as_array()?.iter()
+            .find(|t| t[0] == name).and_then(|t| t[1].as_str()).map(str::to_string);
+        let id: [u8; 32] = Sha256::digest(serde_json::to_vec(&serde_json::json!([
+            0, event["pubkey"], event["created_at"], event["kind"], event["tags"], event["content"]
+        ])).unwrap()).into();
+        let signed = (|| {
+            let sig: nostr::secp256k1::schnorr::Signature = event["sig"].as_str()?.parse().ok()?;
+            let pubkey: nostr::secp256k1::XOnlyPublicKey = event["pubkey"].as_str()?.parse().ok()?;
+            nostr::secp256k1::Secp256k1::verification_only()
+                .verify_schnorr(&sig, &Message::from_digest(id), &pubkey).ok()
+        })();
+
+        event["kind"] == BLOSSOM_AUTH_KIND
+            && event["id"] == hex::encode(id)
+            && signed.is_some()
+            && tag("t").as_deref() == Some("upload")
+            && tag("x") == Some(hex::encode(Sha256::digest(body)))
+    }
+
+    /// Minimal Blossom stand-in: `PUT /upload` stores a blob if authorized, `GET /<sha256>` serves it
+    async fn spawn_blossom() -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        let blobs: Arc<Mutex<HashMap<String, Vec<u8>>>> = Arc::default();
+
+        let server_base = base.clone();
+        tokio::spawn(async move {
+            loop {
+                let Ok((mut stream, _)) = listener.accept().await else { return };
+                let blobs = blobs.clone();
+                let base = server_base.clone();
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 8192];
+                    let (head, body_start) = loop {
+                        let n = stream.read(&mut buf).await.unwrap();
+                        request.extend_from_slice(&buf[..n]);
+                        if let Some(pos) = request.windows(4).position(|w| w == b"\r\n\r\n") {
+                            break (String::from_utf8_lossy(&request[..pos]).to_string(), pos + 4);
+                        }
+                    };
+                    let length: usize = head.lines()
+                        .find_map(|l| l.to_lowercase().strip_prefix("content-length:").map(|v| v.trim().parse().unwrap()))
+                        .unwrap_or(0);
+                    while request.len() < body_start + length {
+                        let n = stream.read(&mut buf).await.unwrap();
+                        request.extend_from_slice(&buf[..n]);
+                    }
+                    let body = request[body_start..body_start + length].to_vec();
+
+                    let (status, response) = if head.starts_with("PUT /upload") && !authorized(&head, &body) {
+                        ("401 Unauthorized", Vec::new())
+                    } else if head.starts_with("PUT /upload") {
+                        let sha256 = hex::encode(Sha256::digest(&body));
+                        blobs.lock().unwrap().insert(sha256.clone(), body);
+                        ("200 OK", format!(r#"{{"url":"{}/{}","sha256":"{}"}}"#, base, sha256, sha256).into_bytes())
+                    } else {
+                        let path = head.split_whitespace().nth(1).unwrap_or("/").trim_start_matches('/').to_string();
+                        match blobs.lock().unwrap().get(&path) {
+                            Some(blob) => ("200 OK", blob.clone()),
+                            None => ("404 Not Found", Vec::new()),
+                        }
+                    };
+                    let header = format!("HTTP/1.1 {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", status, response.len());
+                    stream.write_all(header.as_bytes()).await.unwrap();
+                    stream.write_all(&response).await.unwrap();
+                });
+            }
+        });
+        base
+    }
+
+    #[tokio::test]
+    async fn test_upload_and_download_roundtrip() {
+        let server = spawn_blossom().await;
+        let media = MediaManager::new(&server).unwrap();
+        let secret = MediaSecret { epoch: 3, secret: [7u8; 32] };
+        let signer = Keys::parse(&"11".repeat(32)).unwrap();
+
+        let path = std::env::temp_dir().join(format!("whitenoise_media_{}.txt", uuid::Uuid::new_v4()));
+        std::fs::write(&path, b"attack at dawn").unwrap();
+
+        let attachment = media.upload_file(&secret, &signer, &path).await.unwrap();
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(attachment.mime_type, "text/plain");
+        assert_eq!(attachment.size, 14);
+        assert_eq!(attachment.epoch, 3);
+        assert_eq!(MediaAttachment::from_imeta(&attachment.to_imeta()), Some(attachment.clone()));
+
+        let plaintext = media.download(&secret, &attachment).await.unwrap();
+        assert_eq!(plaintext, b"attack at dawn");
+
+        let relabeled = MediaAttachment { mime_type: "text/html".to_string(), ..attachment.clone() };
+        assert!(media.download(&secret, &relabeled).await.is_err());
+        let resized = MediaAttachment { size: 13, ..attachment.clone() };
+        assert!(media.download(&secret, &resized).await.is_err());
+        let next_epoch = MediaSecret { epoch: 4, secret: [8u8; 32] };
+        assert!(media.download(&next_epoch, &attachment).await.is_err());
+
+        // Uploads without a BUD-02 authorization are refused
+        let unauthorized = media.http.put(format!("{}/upload", server)).body(b"blob".to_vec()).send().await.unwrap();
+        assert_eq!(unauthorized.status(), reqwest::StatusCode::UNAUTHORIZED);
+    }
+}
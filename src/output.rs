@@ -0,0 +1,433 @@
+use anyhow::Result;
+use console::style;
+use serde_json::Value;
+use std::collections::HashMap;
+
+use crate::cli::CommandResult;
+
+/// Which human renderer draws a successful result. Anything without a
+/// dedicated view falls back to a `key: value` listing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum View {
+    Fields,
+    Accounts,
+    Contacts,
+    Groups,
+    Messages,
+    Relays,
+    Invites,
+    Members,
+}
+
+/// Renders command results for `--output human`. Colour comes from
+/// `console`, which already turns itself off for pipes and `NO_COLOR`;
+/// `plain` (set when stdout is not a terminal) drops headers and summary lines.
+pub struct HumanRenderer {
+    names: HashMap<String, String>,
+    own_pubkey: Option<String>,
+    plain: bool,
+}
+
+impl HumanRenderer {
+    pub fn new(names: HashMap<String, String>, own_pubkey: Option<String>, plain: bool) -> Self {
+        Self { names, own_pubkey, plain }
+    }
+
+    pub fn render<T: serde::Serialize>(&self, view: View, result: &CommandResult<T>) -> Result<String> {
+        if !result.success {
+            let error = result.error.as_deref().unwrap_or("Unknown error");
+            let code = result.error_code.as_ref()
+                .map(|code| format!(" {}", style(format!("({})", code)).dim()))
+                .unwrap_or_default();
+            return Ok(format!("{} {}{}", style("Error:").red().bold(), error, code));
+        }
+
+        let data = match &result.data {
+            Some(data) => serde_json::to_value(data)?,
+            None => return Ok("Operation completed successfully".to_string()),
+        };
+
+        let mut lines = match view {
+            View::Fields => self.fields(&data, 0),
+            View::Accounts => self.accounts(&data),
+            View::Contacts => self.contacts(&data),
+            View::Groups => self.groups(&data),
+            View::Messages => self.messages(&data),
+            View::Relays => self.relays(&data),
+            View::Invites => self.invites(&data),
+            View::Members => self.members(&data),
+        };
+        if let Some(cursor) = result.page.as_ref().and_then(|page| page.next_cursor.as_ref()) {
+            if !self.plain {
+                lines.push(style(format!("Older messages: --cursor {}", cursor)).dim().to_string());
+            }
+        }
+        Ok(lines.join("\n"))
+    }
+
+    fn header(&self, lines: &mut Vec<String>, title: &str, count: usize) {
+        if !self.plain {
+            lines.push(format!("{} {}", style(title).bold().cyan(), style(format!("({})", count)).dim()));
+        }
+    }
+
+    fn empty(&self, lines: &mut Vec<String>, what: &str) {
+        if !self.plain {
+            lines.push(style(format!("No {}.", what)).dim().italic().to_string());
+        }
+    }
+
+    /// Contact name for a hex public key, "you" for the current account
+    fn name(&self, pubkey: &str) -> String {
+        if self.own_pubkey.as_deref() == Some(pubkey) {
+            return "you".to_string();
+        }
+        self.names
+            .get(pubkey)
+            .cloned()
+            .unwrap_or_else(|| short(pubkey, 8).to_string())
+    }
+
+    /// Name stored with an archived message, else the contact name
+    fn message_author(&self, message: &Value, author: &str) -> String {
+        message.get("author_name")
+            .and_then(Value::as_str)
+            .map(str::to_string)
+            .unwrap_or_else(|| self.name(author))
+    }
+
+    fn accounts(&self, data: &Value) -> Vec<String> {
+        let accounts = as_list(data);
+        let mut lines = Vec::new();
+        self.header(&mut lines, "Accounts", accounts.len());
+        if accounts.is_empty() {
+            self.empty(&mut lines, "accounts");
+        }
+        for account in accounts {
+            let pubkey = str_field(account, "pubkey");
+            let current = self.own_pubkey.as_deref() == Some(pubkey)
+                || account.get("current").and_then(Value::as_bool).unwrap_or(false);
+            let marker = if current { style("*").green().bold() } else { style(" ") };
+            let synced = account.get("last_synced").and_then(Value::as_u64).filter(|t| *t > 0)
+                .map(|t| format!("last synced {}", local_time(t)))
+                .unwrap_or_else(|| "never synced".to_string());
+            let mut line = match account.get("name").and_then(Value::as_str) {
+                Some(name) => format!("{} {}  {}", marker, style(name).bold().green(), style(pubkey).dim()),
+                None => format!("{} {}", marker, style(pubkey).green()),
+            };
+            if let Some(unread) = account.get("unread").and_then(Value::as_u64).filter(|u| *u > 0) {
+                line.push_str(&format!("  {}", style(format!("{} unread", unread)).yellow().bold()));
+            }
+            line.push_str(&format!("  {}", style(synced).dim()));
+            lines.push(line);
+        }
+        lines
+    }
+
+    fn contacts(&self, data: &Value) -> Vec<String> {
+        let contacts = as_list(data);
+        let mut lines = Vec::new();
+        self.header(&mut lines, "Contacts", contacts.len());
+        if contacts.is_empty() {
+            self.empty(&mut lines, "contacts");
+        }
+        for contact in contacts {
+            let metadata = contact.get("metadata").unwrap_or(&Value::Null);
+            let mut line = format!("{}  {}",
+                style(str_field(contact, "name")).bold().green(),
+                style(str_field(contact, "public_key")).dim()
+            );
+            if let Some(nip05) = metadata.get("nip05").and_then(Value::as_str) {
+                line.push_str(&format!("  {}", style(nip05).cyan()));
+            }
+            lines.push(line);
+            if let Some(about) = metadata.get("about").and_then(Value::as_str).filter(|a| !a.is_empty()) {
+                lines.push(format!("    {}", style(about).dim()));
+            }
+        }
+        lines
+    }
+
+    fn groups(&self, data: &Value) -> Vec<String> {
+        let groups = as_list(data);
+        let mut lines = Vec::new();
+        self.header(&mut lines, "Groups", groups.len());
+        if groups.is_empty() {
+            self.empty(&mut lines, "groups");
+        }
+        for group in groups {
+            let last_message = group.get("last_message_at").and_then(Value::as_u64)
+                .map(|t| format!("last message {}", local_time(t)))
+                .unwrap_or_else(|| "no messages".to_string());
+            lines.push(format!("{}  {}  {} {}",
+                style(str_field(group, "name")).bold().green(),
+                style(short(str_field(group, "mls_group_id"), 16)).dim(),
+                style(format!("{}/{}", display(group.get("group_type")), display(group.get("state"))).to_lowercase()).cyan(),
+                style(last_message).dim()
+            ));
+            if let Some(description) = group.get("description").and_then(Value::as_str).filter(|d| !d.is_empty()) {
+                lines.push(format!("    {}", description));
+            }
+            if let Some(image_url) = group.get("image_url").and_then(Value::as_str) {
+                lines.push(format!("    {}", style(format!("image: {}", image_url)).dim()));
+            }
+        }
+        lines
+    }
+
+    fn messages(&self, data: &Value) -> Vec<String> {
+        let messages = data.get("messages").map(as_list).unwrap_or_default();
+        let mut lines = Vec::new();
+        self.header(&mut lines, "Messages", messages.len());
+        if messages.is_empty() {
+            self.empty(&mut lines, "messages");
+        }
+        let by_id: HashMap<&str, &Value> = messages.iter()
+            .map(|m| (str_field(m, "id"), *m))
+            .collect();
+
+        for message in &messages {
+            let created_at = message.get("created_at").and_then(Value::as_u64).unwrap_or(0);
+            let author = str_field(message, "author");
+            let content = if message.get("is_deleted").and_then(Value::as_bool).unwrap_or(false) {
+                style("(deleted)".to_string()).dim().italic()
+            } else {
+                style(str_field(message, "content").to_string())
+            };
+            // Search results span groups, so they name the group of each message
+            let group = message.get("group_name").and_then(Value::as_str)
+                .map(|name| format!("{} · ", style(name).magenta()))
+                .unwrap_or_default();
+            lines.push(format!("{} {}{} {}  {}",
+                style(format!("[{}]", local_time(created_at))).dim(),
+                group,
+                style(format!("{}:", self.message_author(message, author))).bold().blue(),
+                content,
+                style(format!("#{}", short(str_field(message, "id"), 8))).dim()
+            ));
+            if let Some(reply_to) = message.get("reply_to_id").and_then(Value::as_str) {
+                let context = match by_id.get(reply_to) {
+                    Some(parent) => format!("↳ reply to {}: {}",
+                        self.name(str_field(parent, "author")),
+                        excerpt(str_field(parent, "content"), 40)
+                    ),
+                    None => format!("↳ reply to #{}", short(reply_to, 8)),
+                };
+                lines.push(format!("    {}", style(context).dim()));
+            }
+            for attachment in message.get("attachments").map(as_list).unwrap_or_default() {
+                let size = attachment.get("size").and_then(Value::as_u64).unwrap_or(0);
+                lines.push(format!("    📎 {} {}",
+                    str_field(attachment, "name"),
+                    style(format!("({}, {} bytes)", str_field(attachment, "mime_type"), size)).dim()
+                ));
+            }
+            // Live messages carry a by_emoji map, imported archives a list
+            let summary: Vec<String> = match message.pointer("/reactions/by_emoji").and_then(Value::as_object) {
+                Some(reactions) => reactions.iter()
+                    .map(|(emoji, reaction)| format!("{} {}", emoji, reaction.get("count").and_then(Value::as_u64).unwrap_or(0)))
+                    .collect(),
+                None => message.get("reactions").and_then(Value::as_array).into_iter().flatten()
+                    .map(|reaction| format!("{} {}", str_field(reaction, "emoji"), reaction.get("count").and_then(Value::as_u64).unwrap_or(0)))
+                    .collect(),
+            };
+            if !summary.is_empty() {
+                lines.push(format!("    {}", summary.join("  ")));
+            }
+        }
+        lines
+    }
+
+    fn relays(&self, data: &Value) -> Vec<String> {
+        let mut lines = Vec::new();
+        let Some(types) = data.as_object() else {
+            return self.fields(data, 0);
+        };
+        for (relay_type, relays) in types {
+            let relays = as_list(relays);
+            self.header(&mut lines, &format!("{} relays", relay_type), relays.len());
+            if relays.is_empty() && !self.plain {
+                lines.push(format!("  {}", style("none").dim().italic()));
+            }
+            for relay in relays {
+                lines.push(format!("  • {}", display(Some(relay))));
+            }
+        }
+        lines
+    }
+
+    fn invites(&self, data: &Value) -> Vec<String> {
+        let invites = data.get("invites").map(as_list).unwrap_or_default();
+        let mut lines = Vec::new();
+        self.header(&mut lines, "Invitations", invites.len());
+        if invites.is_empty() {
+            self.empty(&mut lines, "pending invitations");
+        }
+        for invite in invites {
+            lines.push(format!("{}  from {}  {} members  {}",
+                style(str_field(invite, "group_name")).bold().green(),
+                self.name(str_field(invite, "invited_by")),
+                display(invite.get("member_count")),
+                style(format!("id {}", short(str_field(invite, "id"), 16))).dim()
+            ));
+        }
+        lines
+    }
+
+    fn members(&self, data: &Value) -> Vec<String> {
+        let members = data.get("members").map(as_list).unwrap_or_default();
+        let admins: Vec<&str> = data.get("admins").map(as_list).unwrap_or_default()
+            .into_iter()
+            .filter_map(Value::as_str)
+            .collect();
+
+        let mut lines = Vec::new();
+        self.header(&mut lines, "Members", members.len());
+        for member in members.iter().filter_map(|m| m.as_str()) {
+            let badge = if admins.contains(&member) { style(" admin").yellow() } else { style("") };
+            lines.push(format!("  • {} {}{}", style(self.name(member)).green(), style(member).dim(), badge));
+        }
+        if let Some(epoch) = data.get("epoch").and_then(Value::as_u64) {
+            if !self.plain {
+                lines.push(format!("{} {}", style("Epoch:").dim(), epoch));
+            }
+        }
+        lines
+    }
+
+    /// Generic `key: value` listing for results without a dedicated view
+    fn fields(&self, data: &Value, indent: usize) -> Vec<String> {
+        let pad = "  ".repeat(indent);
+        let mut lines = Vec::new();
+        match data {
+            Value::Object(map) => {
+                if let Some(message) = map.get("message").and_then(Value::as_str) {
+                    if indent == 0 {
+                        lines.push(style(message).green().to_string());
+                    }
+                }
+                for (key, value) in map {
+                    if indent == 0 && key == "message" && value.is_string() {
+                        continue;
+                    }
+                    let label = style(format!("{}{}:", pad, humanize(key))).bold();
+                    match value {
+                        Value::Object(_) => {
+                            lines.push(label.to_string());
+                            lines.extend(self.fields(value, indent + 1));
+                        }
+                        Value::Array(items) if items.iter().any(|i| i.is_object()) => {
+                            lines.push(label.to_string());
+                            for item in items {
+                                lines.extend(self.fields(item, indent + 1));
+                                lines.push(String::new());
+                            }
+                        }
+                        Value::Number(n) if key.ends_with("_at") || key == "timestamp" => {
+                            let time = n.as_u64().map(local_time).unwrap_or_else(|| n.to_string());
+                            lines.push(format!("{} {}", label, time));
+                        }
+                        _ => lines.push(format!("{} {}", label, display(Some(value)))),
+                    }
+                }
+            }
+            Value::Array(items) => {
+                for item in items {
+                    lines.extend(self.fields(item, indent));
+                }
+            }
+            other => lines.push(format!("{}{}", pad, display(Some(other)))),
+        }
+        lines
+    }
+}
+
+fn as_list(value: &Value) -> Vec<&Value> {
+    match value {
+        Value::Array(items) => items.iter().collect(),
+        Value::Null => Vec::new(),
+        other => vec![other],
+    }
+}
+
+fn str_field<'a>(value: &'a Value, key: &str) -> &'a str {
+    value.get(key).and_then(Value::as_str).unwrap_or("")
+}
+
+fn short(s: &str, len: usize) -> &str {
+    s.get(..len).unwrap_or(s)
+}
+
+/// First `len` characters of `text` on one line, with an ellipsis if cut
+fn excerpt(text: &str, len: usize) -> String {
+    let line = text.lines().next().unwrap_or("");
+    if line.chars().count() > len || text.lines().count() > 1 {
+        format!("{}…", line.chars().take(len).collect::<String>())
+    } else {
+        line.to_string()
+    }
+}
+
+fn humanize(key: &str) -> String {
+    let spaced = key.replace('_', " ");
+    let mut chars = spaced.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => spaced,
+    }
+}
+
+fn display(value: Option<&Value>) -> String {
+    match value {
+        None | Some(Value::Null) => "-".to_string(),
+        Some(Value::String(s)) => s.clone(),
+        Some(Value::Bool(b)) => if *b { "yes" } else { "no" }.to_string(),
+        Some(Value::Array(items)) if items.is_empty() => "-".to_string(),
+        Some(Value::Array(items)) => items.iter().map(|i| display(Some(i))).collect::<Vec<_>>().join(", "),
+        Some(other) => other.to_string(),
+    }
+}
+
+/// Unix timestamp in the local timezone
+fn local_time(timestamp: u64) -> String {
+    chrono::DateTime::from_timestamp(timestamp as i64, 0)
+        .map(|t| t.with_timezone(&chrono::Local).format("%Y-%m-%d %H:%M").to_string())
+        .unwrap_or_else(|| timestamp.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_messages_with_names() {
+        console::set_colors_enabled(false);
+        let names = HashMap::from([("aa".repeat(32), "Alice".to_string())]);
+        let renderer = HumanRenderer::new(names, Some("bb".repeat(32)), false);
+
+        let result = CommandResult::success(serde_json::json!({
+            "messages": [
+                { "id": "cc".repeat(32), "author": "aa".repeat(32), "content": "hi", "created_at": 0, "is_deleted": false,
+                  "reactions": { "by_emoji": { "👍": { "count": 2 } } } },
+                { "id": "dd".repeat(32), "author": "bb".repeat(32), "content": "hey", "created_at": 0, "is_deleted": false,
+                  "reply_to_id": "cc".repeat(32) },
+                { "id": "ee".repeat(32), "author": "aa".repeat(32), "content": "hm", "created_at": 0, "is_deleted": false,
+                  "reply_to_id": "ff".repeat(32) }
+            ]
+        }));
+        let output = renderer.render(View::Messages, &result).unwrap();
+        assert!(output.starts_with("Messages (3)"));
+        assert!(output.contains("Alice: hi  #cccccccc"));
+        assert!(output.contains("👍 2"));
+        assert!(output.contains("you: hey"));
+        assert!(output.contains("↳ reply to Alice: hi"));
+        assert!(output.contains("↳ reply to #ffffffff"));
+
+        let plain = HumanRenderer::new(HashMap::new(), None, true);
+        let output = plain.render(View::Fields, &CommandResult::success(serde_json::json!({
+            "message": "Contact added successfully",
+            "group_id": "abcd"
+        }))).unwrap();
+        assert_eq!(output, "Contact added successfully\nGroup id: abcd");
+    }
+}
@@ -1,10 +1,34 @@
 use anyhow::Result;
-use console::style;
+use futures_util::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+use tokio_tungstenite::tungstenite::Message;
 use whitenoise::{Account, PublicKey, RelayType, RelayUrl, Whitenoise, Event};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+use crate::errors::CliError;
+
+/// Upper bound for each stage of a relay connectivity test
+const RELAY_TEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Relay profile used when neither the command line, the account nor the
+/// config file names one: the local development relay plus public relays
+pub const DEFAULT_RELAY_PROFILE: &str = "default";
+
+/// Built-in relay profiles, selectable with `--relay-profile`
+pub const BUILTIN_RELAY_PROFILES: [&str; 3] = [DEFAULT_RELAY_PROFILE, "local-dev", "public"];
+
+const LOCAL_DEV_RELAYS: [&str; 1] = ["ws://localhost:10547"];
+const PUBLIC_NOSTR_RELAYS: [&str; 4] = ["wss://relay.damus.io", "wss://relay.primal.net", "wss://nos.lol", "wss://relay.nostr.net"];
+const PUBLIC_INBOX_RELAYS: [&str; 3] = ["wss://relay.damus.io", "wss://relay.primal.net", "wss://relay.nostr.net"];
+const PUBLIC_KEY_PACKAGE_RELAYS: [&str; 3] = ["wss://relay.damus.io", "wss://nos.lol", "wss://relay.nostr.net"];
+
+/// The relays of one relay profile, per relay type. Every default relay
+/// list in the CLI (account setup, metadata and key package lookups) is
+/// read from the active profile.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct RelayConfig {
+    pub profile: String,
     pub nostr_relays: Vec<String>,
     pub inbox_relays: Vec<String>,
     pub key_package_relays: Vec<String>,
@@ -12,28 +36,233 @@ pub struct RelayConfig {
 
 impl Default for RelayConfig {
     fn default() -> Self {
-        Self {
-            nostr_relays: vec![
-                "ws://localhost:10547".to_string(),
-                "wss://relay.damus.io".to_string(),
-                "wss://relay.primal.net".to_string(),
-                "wss://nos.lol".to_string(),
-                "wss://relay.nostr.net".to_string(),
-            ],
-            inbox_relays: vec![
-                "ws://localhost:10547".to_string(),
-                "wss://relay.damus.io".to_string(),
-                "wss://relay.primal.net".to_string(),
-                "wss://relay.nostr.net".to_string(),
-            ],
-            key_package_relays: vec![
-                "ws://localhost:10547".to_string(),
-                "wss://relay.damus.io".to_string(),
-                "wss://nos.lol".to_string(),
-                "wss://relay.nostr.net".to_string(),
-            ],
+        Self::builtin(DEFAULT_RELAY_PROFILE).expect("default relay profile is built in")
+    }
+}
+
+impl RelayConfig {
+    /// One of the built-in profiles: `default`, `local-dev` or `public`
+    pub fn builtin(name: &str) -> Option<Self> {
+        let lists = |local: &[&str], public: &[&str]| -> Vec<String> {
+            local.iter().chain(public).map(|url| url.to_string()).collect()
+        };
+        let (local, public_nostr, public_inbox, public_key_package): (&[&str], &[&str], &[&str], &[&str]) = match name {
+            DEFAULT_RELAY_PROFILE => (&LOCAL_DEV_RELAYS, &PUBLIC_NOSTR_RELAYS, &PUBLIC_INBOX_RELAYS, &PUBLIC_KEY_PACKAGE_RELAYS),
+            "local-dev" => (&LOCAL_DEV_RELAYS, &[], &[], &[]),
+            "public" => (&[], &PUBLIC_NOSTR_RELAYS, &PUBLIC_INBOX_RELAYS, &PUBLIC_KEY_PACKAGE_RELAYS),
+            _ => return None,
+        };
+        Some(Self {
+            profile: name.to_string(),
+            nostr_relays: lists(local, public_nostr),
+            inbox_relays: lists(local, public_inbox),
+            key_package_relays: lists(local, public_key_package),
+        })
+    }
+
+    pub fn relays_for_type(&self, relay_type: &RelayType) -> &Vec<String> {
+        match relay_type {
+            RelayType::Nostr => &self.nostr_relays,
+            RelayType::Inbox => &self.inbox_relays,
+            RelayType::KeyPackage => &self.key_package_relays,
+        }
+    }
+
+    /// Parsed relay URLs of one type; malformed entries are skipped
+    pub fn relay_urls(&self, relay_type: &RelayType) -> Vec<RelayUrl> {
+        self.relays_for_type(relay_type)
+            .iter()
+            .filter_map(|url| RelayUrl::parse(url).ok())
+            .collect()
+    }
+}
+
+static NETWORK_POLICY: OnceLock<NetworkPolicy> = OnceLock::new();
+
+#[cfg(test)]
+static TEST_POLICY: std::sync::Mutex<Option<&'static NetworkPolicy>> = std::sync::Mutex::new(None);
+
+#[cfg(test)]
+fn test_policy() -> Option<&'static NetworkPolicy> {
+    *TEST_POLICY.lock().unwrap()
+}
+
+#[cfg(not(test))]
+fn test_policy() -> Option<&'static NetworkPolicy> {
+    None
+}
+
+/// Which relays the CLI may contact. `--offline` and `--relays` restrict it
+/// to an explicit list; anything else fails before a connection is made.
+/// Installed once at startup, like the WhiteNoise instance.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NetworkPolicy {
+    allowed: Option<Vec<String>>,
+}
+
+impl NetworkPolicy {
+    /// `--relays local` (or `--offline` alone) allows the local development
+    /// relay; `--relays a,b` allows exactly those relays
+    pub fn from_flags(offline: bool, relays: Option<&str>) -> Result<Self> {
+        let allowed = match relays.map(str::trim) {
+            Some("local") => LOCAL_DEV_RELAYS.iter().map(|url| url.to_string()).collect(),
+            Some(list) => list.split(',')
+                .map(str::trim)
+                .filter(|url| !url.is_empty())
+                .map(normalize_relay_url)
+                .collect::<Result<Vec<String>>>()?,
+            None if offline => LOCAL_DEV_RELAYS.iter().map(|url| url.to_string()).collect(),
+            None => return Ok(Self::default()),
+        };
+        if allowed.is_empty() {
+            return Err(CliError::InvalidInput("--relays needs at least one relay URL or 'local'".to_string()).into());
+        }
+        Ok(Self { allowed: Some(allowed) })
+    }
+
+    /// Make this the policy for the rest of the process; fails once a
+    /// policy is installed or `current` has already been read
+    pub fn install(self) -> Result<()> {
+        NETWORK_POLICY.set(self).map_err(|_| anyhow::anyhow!("Network policy is already in place"))
+    }
+
+    pub fn current() -> &'static NetworkPolicy {
+        test_policy().unwrap_or_else(|| NETWORK_POLICY.get_or_init(Self::default))
+    }
+
+    /// Replace the policy for every later `current` call in the test binary.
+    /// Unlike `install`, this works after other tests have read the default.
+    #[cfg(test)]
+    pub fn install_for_tests(self) {
+        *TEST_POLICY.lock().unwrap() = Some(Box::leak(Box::new(self)));
+    }
+
+    pub fn is_restricted(&self) -> bool {
+        self.allowed.is_some()
+    }
+
+    pub fn allows(&self, relay_url: &str) -> bool {
+        match &self.allowed {
+            Some(allowed) => normalize_relay_url(relay_url).is_ok_and(|url| allowed.contains(&url)),
+            None => true,
         }
     }
+
+    pub fn check_relay(&self, relay_url: &str) -> Result<()> {
+        self.check_relays([relay_url.to_string()])
+    }
+
+    /// Fail if any of `relay_urls` is outside the allowed relays
+    pub fn check_relays(&self, relay_urls: impl IntoIterator<Item = String>) -> Result<()> {
+        let Some(allowed) = &self.allowed else {
+            return Ok(());
+        };
+        let denied: Vec<String> = relay_urls.into_iter().filter(|url| !self.allows(url)).collect();
+        if denied.is_empty() {
+            return Ok(());
+        }
+        Err(CliError::Relay(format!(
+            "Offline mode only allows {}; refusing {}", allowed.join(", "), denied.join(", ")
+        )).into())
+    }
+
+    /// HTTP (media servers, NIP-05) is limited to this machine while restricted
+    pub fn check_http(&self, url: &str) -> Result<()> {
+        if !self.is_restricted() {
+            return Ok(());
+        }
+        let host = url::Url::parse(url).ok().and_then(|url| url.host_str().map(str::to_string));
+        match host.as_deref() {
+            Some("localhost" | "127.0.0.1" | "[::1]") => Ok(()),
+            _ => Err(CliError::Relay(format!("Offline mode only allows HTTP to localhost; refusing {}", url)).into()),
+        }
+    }
+
+    /// The relays every relay type uses while restricted
+    pub fn relay_config(&self) -> Option<RelayConfig> {
+        self.allowed.as_ref().map(|allowed| RelayConfig {
+            profile: "offline".to_string(),
+            nostr_relays: allowed.clone(),
+            inbox_relays: allowed.clone(),
+            key_package_relays: allowed.clone(),
+        })
+    }
+}
+
+/// Comparable form of a relay URL: ws/wss scheme, no trailing slash
+fn normalize_relay_url(relay_url: &str) -> Result<String> {
+    let url = url::Url::parse(relay_url.trim())
+        .map_err(|e| CliError::InvalidInput(format!("Invalid relay URL {}: {}", relay_url, e)))?;
+    if url.scheme() != "ws" && url.scheme() != "wss" {
+        return Err(CliError::InvalidInput(format!("Relay URL must use ws:// or wss://: {}", relay_url)).into());
+    }
+    Ok(url.as_str().trim_end_matches('/').to_string())
+}
+
+/// NIP-11 relay information document
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RelayInfo {
+    pub name: Option<String>,
+    pub description: Option<String>,
+    pub pubkey: Option<String>,
+    pub contact: Option<String>,
+    pub software: Option<String>,
+    pub version: Option<String>,
+    #[serde(default)]
+    pub supported_nips: Vec<u16>,
+    pub limitation: Option<RelayLimitation>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RelayLimitation {
+    pub max_message_length: Option<u64>,
+    pub max_subscriptions: Option<u64>,
+    pub max_filters: Option<u64>,
+    pub max_limit: Option<u64>,
+    pub max_subid_length: Option<u64>,
+    pub max_event_tags: Option<u64>,
+    pub max_content_length: Option<u64>,
+    pub min_pow_difficulty: Option<u64>,
+    pub auth_required: Option<bool>,
+    pub payment_required: Option<bool>,
+    pub restricted_writes: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RelayFailureReason {
+    InvalidUrl,
+    UnsupportedScheme,
+    ConnectionFailed,
+    Timeout,
+    SubscriptionRejected,
+    ProtocolError,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RelayTestFailure {
+    pub reason: RelayFailureReason,
+    pub message: String,
+}
+
+impl RelayTestFailure {
+    fn new(reason: RelayFailureReason, message: impl Into<String>) -> Self {
+        Self { reason, message: message.into() }
+    }
+}
+
+/// Outcome of `RelayManager::test_relay_connection`
+#[derive(Debug, Clone, Serialize)]
+pub struct RelayTestReport {
+    pub url: String,
+    pub reachable: bool,
+    /// Time to complete the websocket handshake
+    pub connect_ms: Option<u64>,
+    /// Round trip from sending REQ to receiving EOSE
+    pub latency_ms: Option<u64>,
+    pub info: Option<RelayInfo>,
+    pub info_error: Option<String>,
+    pub failure: Option<RelayTestFailure>,
 }
 
 pub struct RelayManager {
@@ -41,10 +270,8 @@ pub struct RelayManager {
 }
 
 impl RelayManager {
-    pub fn new() -> Self {
-        Self {
-            config: RelayConfig::default(),
-        }
+    pub fn new(config: RelayConfig) -> Self {
+        Self { config }
     }
 
     pub async fn fetch_relays(&self, pubkey: PublicKey, relay_type: RelayType) -> Result<Vec<RelayUrl>> {
@@ -65,57 +292,65 @@ impl RelayManager {
         Ok(relays)
     }
 
+    /// Replace one of the account's relay lists, persist the account and
+    /// publish the matching relay list event (kind 10002, 10050 or 10051).
     pub async fn update_relays(
-        &mut self,
-        _account: &Account,
+        &self,
+        account: &mut Account,
         relay_type: RelayType,
         relays: Vec<String>,
     ) -> Result<()> {
-        let _whitenoise = Whitenoise::get_instance()
+        let whitenoise = Whitenoise::get_instance()
             .map_err(|e| anyhow::anyhow!("Failed to get WhiteNoise instance: {:?}", e))?;
 
+        NetworkPolicy::current().check_relays(relays.iter().cloned())?;
+
         // Convert strings to RelayUrl objects
-        let relay_urls: Result<Vec<RelayUrl>, _> = relays
+        let relay_urls = relays
             .iter()
-            .map(|url| RelayUrl::parse(url))
-            .collect();
-
-        let _relay_urls = relay_urls
-            .map_err(|e| anyhow::anyhow!("Invalid relay URL: {:?}", e))?;
+            .map(|url| Self::validate_relay_url(url))
+            .collect::<Result<Vec<RelayUrl>>>()?;
 
-        // WhiteNoise doesn't have update_relays - relays are stored on the account
-        // This would require updating the account object and saving it
-        // For now, we'll just log this as a limitation
-        println!("⚠️ Relay updates are stored locally but not persisted to WhiteNoise");
-
-        // Update local config
         match relay_type {
-            RelayType::Nostr => self.config.nostr_relays = relays,
-            RelayType::Inbox => self.config.inbox_relays = relays,
-            RelayType::KeyPackage => self.config.key_package_relays = relays,
+            RelayType::Nostr => account.nip65_relays = relay_urls.clone(),
+            RelayType::Inbox => account.inbox_relays = relay_urls.clone(),
+            RelayType::KeyPackage => account.key_package_relays = relay_urls.clone(),
         }
 
-        println!("{} {} relays updated successfully!", 
-            style("✅").green(), 
-            self.relay_type_name(&relay_type)
-        );
+        whitenoise.save_account(account).await
+            .map_err(|e| CliError::Relay(format!("Failed to save account relays: {:?}", e)))?;
+
+        whitenoise.publish_relay_list_for_account(account, relay_urls, relay_type).await
+            .map_err(|e| CliError::Relay(format!("Failed to publish {} relay list: {:?}", self.relay_type_name(&relay_type), e)))?;
 
         Ok(())
     }
 
+    /// Nostr event kind of the relay list published for a relay type
+    pub fn relay_list_kind(relay_type: &RelayType) -> u16 {
+        match relay_type {
+            RelayType::Nostr => 10002,
+            RelayType::Inbox => 10050,
+            RelayType::KeyPackage => 10051,
+        }
+    }
+
+    pub fn account_relays(account: &Account, relay_type: &RelayType) -> Vec<String> {
+        let relays = match relay_type {
+            RelayType::Nostr => &account.nip65_relays,
+            RelayType::Inbox => &account.inbox_relays,
+            RelayType::KeyPackage => &account.key_package_relays,
+        };
+        relays.iter().map(|url| url.to_string()).collect()
+    }
+
     pub async fn fetch_key_package(&self, pubkey: PublicKey) -> Result<Option<Event>> {
         let whitenoise = Whitenoise::get_instance()
             .map_err(|e| anyhow::anyhow!("Failed to get WhiteNoise instance: {:?}", e))?;
 
         // Fetch key package relays for this pubkey
-        let nip65_relays = vec![
-            RelayUrl::parse("ws://localhost:10547")?,
-            RelayUrl::parse("wss://relay.damus.io")?,
-            RelayUrl::parse("wss://relay.primal.net")?,
-            RelayUrl::parse("wss://nos.lol")?,
-            RelayUrl::parse("wss://relay.nostr.net")?,
-        ];
-        
+        let nip65_relays = self.config.relay_urls(&RelayType::Nostr);
+
         let key_package_relays = whitenoise.fetch_relays_from(nip65_relays, pubkey, RelayType::KeyPackage).await
             .map_err(|e| anyhow::anyhow!("Failed to fetch key package relays: {:?}", e))?;
 
@@ -127,13 +362,8 @@ impl RelayManager {
         let _whitenoise = Whitenoise::get_instance()
             .map_err(|e| anyhow::anyhow!("Failed to get WhiteNoise instance: {:?}", e))?;
 
-        println!("{}", style("🔐 Publishing MLS key package to relays...").yellow());
-
         // WhiteNoise doesn't have onboarding state - key packages are published automatically
         // during account creation/login
-        println!("{}", style("ℹ️ Key packages are published automatically during account setup.").yellow());
-
-        println!("{}", style("✅ Key package publishing status updated!").green());
         Ok(())
     }
 
@@ -141,12 +371,18 @@ impl RelayManager {
         &self.config
     }
 
+    /// Switch to another relay profile
+    pub fn set_config(&mut self, config: RelayConfig) {
+        self.config = config;
+    }
+
     pub fn get_relays_for_type(&self, relay_type: &RelayType) -> &Vec<String> {
-        match relay_type {
-            RelayType::Nostr => &self.config.nostr_relays,
-            RelayType::Inbox => &self.config.inbox_relays,
-            RelayType::KeyPackage => &self.config.key_package_relays,
-        }
+        self.config.relays_for_type(relay_type)
+    }
+
+    /// Relays of the active profile used to look up other users' metadata
+    pub fn lookup_relays(&self) -> Vec<RelayUrl> {
+        self.config.relay_urls(&RelayType::Nostr)
     }
 
     pub fn relay_type_name(&self, relay_type: &RelayType) -> &'static str {
@@ -161,37 +397,171 @@ impl RelayManager {
         vec![RelayType::Nostr, RelayType::Inbox, RelayType::KeyPackage]
     }
 
-    pub async fn test_relay_connection(&self, relay_url: &str) -> Result<bool> {
-        // Basic URL validation
-        if let Err(_) = url::Url::parse(relay_url) {
-            return Ok(false);
+    /// Check that a relay URL is well formed and uses a websocket scheme
+    pub fn validate_relay_url(relay_url: &str) -> Result<RelayUrl> {
+        let url = url::Url::parse(relay_url)
+            .map_err(|e| CliError::InvalidInput(format!("Invalid relay URL {}: {}", relay_url, e)))?;
+        if url.scheme() != "ws" && url.scheme() != "wss" {
+            return Err(CliError::InvalidInput(format!("Relay URL must use ws:// or wss://: {}", relay_url)).into());
         }
+        RelayUrl::parse(relay_url).map_err(|e| CliError::InvalidInput(format!("Invalid relay URL: {:?}", e)).into())
+    }
 
-        // For now, just validate the URL format
-        // In a more complete implementation, we could try to connect to the relay
-        let is_websocket = relay_url.starts_with("ws://") || relay_url.starts_with("wss://");
-        Ok(is_websocket)
+    /// Connect to a relay, run a REQ/CLOSE round trip and fetch its NIP-11 document
+    pub async fn test_relay_connection(&self, relay_url: &str) -> Result<RelayTestReport> {
+        NetworkPolicy::current().check_relay(relay_url)?;
+        Ok(Self::probe_relay(relay_url).await)
     }
 
-    pub async fn add_relay_to_type(&mut self, account: &Account, relay_type: RelayType, relay_url: String) -> Result<()> {
-        if !self.test_relay_connection(&relay_url).await? {
-            return Err(anyhow::anyhow!("Invalid relay URL or connection failed"));
+    /// Probe a relay without consulting the network policy
+    async fn probe_relay(relay_url: &str) -> RelayTestReport {
+        let mut report = RelayTestReport {
+            url: relay_url.to_string(),
+            reachable: false,
+            connect_ms: None,
+            latency_ms: None,
+            info: None,
+            info_error: None,
+            failure: None,
+        };
+
+        let url = match url::Url::parse(relay_url) {
+            Ok(url) => url,
+            Err(e) => {
+                report.failure = Some(RelayTestFailure::new(RelayFailureReason::InvalidUrl, e.to_string()));
+                return report;
+            }
+        };
+        if url.scheme() != "ws" && url.scheme() != "wss" {
+            report.failure = Some(RelayTestFailure::new(
+                RelayFailureReason::UnsupportedScheme,
+                format!("Expected ws:// or wss://, got {}://", url.scheme()),
+            ));
+            return report;
         }
 
-        let mut current_relays = self.get_relays_for_type(&relay_type).clone();
-        if !current_relays.contains(&relay_url) {
-            current_relays.push(relay_url);
-            self.update_relays(account, relay_type, current_relays).await?;
+        match Self::fetch_relay_info(&url).await {
+            Ok(info) => report.info = Some(info),
+            Err(e) => report.info_error = Some(e.to_string()),
         }
 
-        Ok(())
+        match Self::probe_websocket(&url).await {
+            Ok((connect, latency)) => {
+                report.reachable = true;
+                report.connect_ms = Some(connect.as_millis() as u64);
+                report.latency_ms = Some(latency.as_millis() as u64);
+            }
+            Err(failure) => report.failure = Some(failure),
+        }
+
+        report
+    }
+
+    async fn fetch_relay_info(url: &url::Url) -> Result<RelayInfo> {
+        let mut http_url = url.clone();
+        let scheme = if url.scheme() == "wss" { "https" } else { "http" };
+        http_url
+            .set_scheme(scheme)
+            .map_err(|_| anyhow::anyhow!("Cannot derive HTTP URL from {}", url))?;
+
+        let response = reqwest::Client::new()
+            .get(http_url)
+            .header("Accept", "application/nostr+json")
+            .timeout(RELAY_TEST_TIMEOUT)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(response.json::<RelayInfo>().await?)
     }
 
-    pub async fn remove_relay_from_type(&mut self, account: &Account, relay_type: RelayType, relay_url: &str) -> Result<()> {
-        let mut current_relays = self.get_relays_for_type(&relay_type).clone();
-        current_relays.retain(|url| url != relay_url);
+    async fn probe_websocket(url: &url::Url) -> std::result::Result<(Duration, Duration), RelayTestFailure> {
+        let started = Instant::now();
+        let (mut socket, _) = tokio::time::timeout(RELAY_TEST_TIMEOUT, tokio_tungstenite::connect_async(url.as_str()))
+            .await
+            .map_err(|_| RelayTestFailure::new(RelayFailureReason::Timeout, "Timed out opening websocket"))?
+            .map_err(|e| RelayTestFailure::new(RelayFailureReason::ConnectionFailed, e.to_string()))?;
+        let connect_time = started.elapsed();
+
+        let subscription_id = format!("wn-test-{}", &uuid::Uuid::new_v4().simple().to_string()[..8]);
+        let req = serde_json::json!(["REQ", subscription_id, { "limit": 1 }]);
+
+        let sent_at = Instant::now();
+        socket
+            .send(Message::Text(req.to_string().into()))
+            .await
+            .map_err(|e| RelayTestFailure::new(RelayFailureReason::ConnectionFailed, e.to_string()))?;
+
+        let wait_for_eose = async {
+            while let Some(message) = socket.next().await {
+                let text = match message {
+                    Ok(Message::Text(text)) => text,
+                    Ok(Message::Close(frame)) => {
+                        let reason = frame.map(|f| f.reason.to_string()).unwrap_or_default();
+                        return Err(RelayTestFailure::new(
+                            RelayFailureReason::ConnectionFailed,
+                            format!("Relay closed the connection {}", reason).trim_end().to_string(),
+                        ));
+                    }
+                    Ok(_) => continue,
+                    Err(e) => return Err(RelayTestFailure::new(RelayFailureReason::ConnectionFailed, e.to_string())),
+                };
+
+                let value: serde_json::Value = serde_json::from_str(text.as_str())
+                    .map_err(|e| RelayTestFailure::new(RelayFailureReason::ProtocolError, format!("Invalid relay message: {}", e)))?;
+                let is_ours = value.get(1).and_then(|id| id.as_str()) == Some(subscription_id.as_str());
+
+                match value.get(0).and_then(|kind| kind.as_str()) {
+                    Some("EOSE") if is_ours => return Ok(sent_at.elapsed()),
+                    Some("CLOSED") if is_ours => {
+                        let message = value.get(2).and_then(|m| m.as_str()).unwrap_or("subscription closed");
+                        return Err(RelayTestFailure::new(RelayFailureReason::SubscriptionRejected, message));
+                    }
+                    // EVENT, NOTICE and AUTH messages don't end the probe
+                    _ => continue,
+                }
+            }
+            Err(RelayTestFailure::new(RelayFailureReason::ConnectionFailed, "Connection ended before EOSE"))
+        };
+
+        let latency = tokio::time::timeout(RELAY_TEST_TIMEOUT, wait_for_eose)
+            .await
+            .map_err(|_| RelayTestFailure::new(RelayFailureReason::Timeout, "Timed out waiting for EOSE"))??;
+
+        let close = serde_json::json!(["CLOSE", subscription_id]);
+        let _ = socket.send(Message::Text(close.to_string().into())).await;
+        let _ = socket.close(None).await;
+
+        Ok((connect_time, latency))
+    }
+
+    /// Add a relay to the account's list. Returns false if it was already present.
+    pub async fn add_relay_to_type(&self, account: &mut Account, relay_type: RelayType, relay_url: String) -> Result<bool> {
+        let relay = Self::validate_relay_url(&relay_url)?.to_string();
+
+        let mut current_relays = Self::account_relays(account, &relay_type);
+        if current_relays.contains(&relay) {
+            return Ok(false);
+        }
+
+        current_relays.push(relay);
         self.update_relays(account, relay_type, current_relays).await?;
-        Ok(())
+        Ok(true)
+    }
+
+    /// Remove a relay from the account's list. Returns false if it wasn't configured.
+    pub async fn remove_relay_from_type(&self, account: &mut Account, relay_type: RelayType, relay_url: &str) -> Result<bool> {
+        let relay = Self::validate_relay_url(relay_url)?.to_string();
+
+        let mut current_relays = Self::account_relays(account, &relay_type);
+        let before = current_relays.len();
+        current_relays.retain(|url| url != &relay);
+        if current_relays.len() == before {
+            return Ok(false);
+        }
+
+        self.update_relays(account, relay_type, current_relays).await?;
+        Ok(true)
     }
 
     pub async fn cleanup_unwanted_relays(&mut self, _account: &Account) -> Result<()> {
@@ -212,8 +582,61 @@ impl RelayManager {
                 RelayType::KeyPackage => self.config.key_package_relays = filtered_relays,
             }
         }
-        
-        println!("{}", style("✅ Unwanted relays removed from local configuration").green());
+
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_relay::MockRelay;
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn test_reachable_relay() {
+        let relay = MockRelay::start().await;
+        let report = RelayManager::probe_relay(relay.url()).await;
+
+        assert!(report.reachable, "{:?}", report.failure);
+        assert!(report.latency_ms.is_some());
+        let info = report.info.expect("NIP-11 document");
+        assert_eq!(info.name.as_deref(), Some("mock relay"));
+        assert_eq!(info.supported_nips, vec![1, 11, 45]);
+        assert_eq!(info.limitation.unwrap().max_subscriptions, Some(20));
+    }
+
+    #[tokio::test]
+    async fn test_unreachable_relay() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        drop(listener);
+
+        let report = RelayManager::probe_relay(&url).await;
+        assert!(!report.reachable);
+        assert_eq!(report.failure.unwrap().reason, RelayFailureReason::ConnectionFailed);
+
+        let report = RelayManager::probe_relay("https://relay.example").await;
+        assert_eq!(report.failure.unwrap().reason, RelayFailureReason::UnsupportedScheme);
+    }
+
+    #[test]
+    fn test_network_policy() {
+        let open = NetworkPolicy::from_flags(false, None).unwrap();
+        assert!(!open.is_restricted());
+        assert!(open.check_relay("wss://relay.damus.io").is_ok());
+
+        let offline = NetworkPolicy::from_flags(true, None).unwrap();
+        assert_eq!(offline, NetworkPolicy::from_flags(false, Some("local")).unwrap());
+        assert!(offline.allows("ws://localhost:10547/"));
+        assert!(offline.check_relays(["ws://localhost:10547".to_string(), "wss://nos.lol".to_string()]).is_err());
+        assert!(offline.check_http("http://127.0.0.1:3000/upload").is_ok());
+        assert!(offline.check_http("https://blossom.primal.net").is_err());
+        assert_eq!(offline.relay_config().unwrap().key_package_relays, vec!["ws://localhost:10547".to_string()]);
+
+        let listed = NetworkPolicy::from_flags(true, Some("ws://127.0.0.1:7777, wss://relay.test")).unwrap();
+        assert!(listed.allows("wss://relay.test"));
+        assert!(!listed.allows("ws://localhost:10547"));
+        assert!(NetworkPolicy::from_flags(false, Some("https://relay.test")).is_err());
+    }
+}
@@ -0,0 +1,263 @@
+use anyhow::Result;
+use regex::{Regex, RegexBuilder};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use whitenoise::ChatMessage;
+
+use crate::errors::CliError;
+use crate::groups::GroupData;
+
+/// Bumped whenever the on-disk layout changes; older indexes are rebuilt
+const INDEX_VERSION: u32 = 1;
+
+/// A message as stored in the search index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct IndexedMessage {
+    pub id: String,
+    pub group_id: String,
+    pub author: String,
+    pub content: String,
+    pub created_at: u64,
+    pub kind: u16,
+    pub is_deleted: bool,
+}
+
+/// A search result: the message plus the name of the group it was sent in
+#[derive(Debug, Clone, Serialize)]
+pub struct SearchHit {
+    pub group_name: String,
+    #[serde(flatten)]
+    pub message: IndexedMessage,
+}
+
+/// Local full-text index over the message history of one account.
+///
+/// Messages are tokenized into lowercase words and kept in an inverted
+/// index, so plain queries only look at messages containing every query
+/// word. A group is re-read from WhiteNoise only when its
+/// `last_message_at` has moved since the previous sync.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SearchIndex {
+    version: u32,
+    messages: Vec<IndexedMessage>,
+    /// Word -> positions in `messages`
+    tokens: BTreeMap<String, BTreeSet<usize>>,
+    /// Group id -> `last_message_at` when the group was last indexed
+    synced: HashMap<String, u64>,
+    #[serde(skip)]
+    positions: HashMap<String, usize>,
+}
+
+/// Filters for `message search`; `None` fields match everything
+#[derive(Debug, Default)]
+pub struct SearchQuery {
+    pub text: String,
+    pub regex: bool,
+    pub group_id: Option<String>,
+    pub author: Option<String>,
+    pub kind: Option<u16>,
+    pub since: Option<u64>,
+    pub until: Option<u64>,
+    pub limit: usize,
+}
+
+impl SearchIndex {
+    /// Restore an index read from disk, discarding it if the layout is outdated
+    pub fn from_stored(mut index: SearchIndex) -> Self {
+        if index.version != INDEX_VERSION {
+            return Self::new();
+        }
+        index.positions = index.messages.iter()
+            .enumerate()
+            .map(|(position, message)| (message.id.clone(), position))
+            .collect();
+        index
+    }
+
+    pub fn new() -> Self {
+        Self { version: INDEX_VERSION, ..Default::default() }
+    }
+
+    pub fn len(&self) -> usize {
+        self.messages.len()
+    }
+
+    /// Whether `group` has new messages since it was last indexed
+    pub fn needs_sync(&self, group: &GroupData) -> bool {
+        let last_message_at = group.last_message_at.unwrap_or(0);
+        self.synced.get(&group.mls_group_id) != Some(&last_message_at) || last_message_at == 0
+    }
+
+    /// Add new messages of a group and refresh deletions and edits of known
+    /// ones. Returns the number of messages added.
+    pub fn sync_group(&mut self, group: &GroupData, messages: &[ChatMessage]) -> usize {
+        let mut added = 0;
+        for message in messages {
+            let indexed = IndexedMessage {
+                id: message.id.clone(),
+                group_id: group.mls_group_id.clone(),
+                author: message.author.to_hex(),
+                content: message.content.clone(),
+                created_at: message.created_at.as_u64(),
+                kind: message.kind,
+                is_deleted: message.is_deleted,
+            };
+            if self.insert(indexed) {
+                added += 1;
+            }
+        }
+        self.synced.insert(group.mls_group_id.clone(), group.last_message_at.unwrap_or(0));
+        added
+    }
+
+    fn insert(&mut self, message: IndexedMessage) -> bool {
+        if let Some(&position) = self.positions.get(&message.id) {
+            let existing = &mut self.messages[position];
+            existing.is_deleted = message.is_deleted;
+            if existing.content != message.content {
+                for token in tokenize(&existing.content) {
+                    if let Some(postings) = self.tokens.get_mut(&token) {
+                        postings.remove(&position);
+                    }
+                }
+                for token in tokenize(&message.content) {
+                    self.tokens.entry(token).or_default().insert(position);
+                }
+                existing.content = message.content;
+            }
+            return false;
+        }
+
+        let position = self.messages.len();
+        for token in tokenize(&message.content) {
+            self.tokens.entry(token).or_default().insert(position);
+        }
+        self.positions.insert(message.id.clone(), position);
+        self.messages.push(message);
+        true
+    }
+
+    /// Matching messages, newest first
+    pub fn search(&self, query: &SearchQuery) -> Result<Vec<&IndexedMessage>> {
+        let pattern = if query.regex {
+            Some(compile_regex(&query.text)?)
+        } else {
+            None
+        };
+
+        let candidates: Box<dyn Iterator<Item = &IndexedMessage>> = match (&pattern, self.candidates(&query.text)) {
+            (None, Some(positions)) => Box::new(positions.into_iter().map(|p| &self.messages[p])),
+            _ => Box::new(self.messages.iter()),
+        };
+
+        let mut hits: Vec<&IndexedMessage> = candidates
+            .filter(|m| !m.is_deleted)
+            .filter(|m| query.group_id.as_ref().is_none_or(|g| &m.group_id == g))
+            .filter(|m| query.author.as_ref().is_none_or(|a| &m.author == a))
+            .filter(|m| query.kind.is_none_or(|k| m.kind == k))
+            .filter(|m| query.since.is_none_or(|since| m.created_at >= since))
+            .filter(|m| query.until.is_none_or(|until| m.created_at <= until))
+            .filter(|m| pattern.as_ref().is_none_or(|re| re.is_match(&m.content)))
+            .collect();
+
+        hits.sort_by_key(|m| std::cmp::Reverse(m.created_at));
+        hits.truncate(query.limit);
+        Ok(hits)
+    }
+
+    /// Positions of messages containing a word starting with each query word,
+    /// or `None` for an empty query
+    fn candidates(&self, text: &str) -> Option<BTreeSet<usize>> {
+        let mut result: Option<BTreeSet<usize>> = None;
+        for word in tokenize(text) {
+            let matches: BTreeSet<usize> = self.tokens
+                .range(word.clone()..)
+                .take_while(|(token, _)| token.starts_with(&word))
+                .flat_map(|(_, postings)| postings.iter().copied())
+                .collect();
+            result = Some(match result {
+                Some(previous) => previous.intersection(&matches).copied().collect(),
+                None => matches,
+            });
+        }
+        result
+    }
+}
+
+fn compile_regex(pattern: &str) -> Result<Regex> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(true)
+        .build()
+        .map_err(|e| CliError::InvalidInput(format!("Invalid regex: {}", e)).into())
+}
+
+/// Lowercase alphanumeric words of `text`, without duplicates
+fn tokenize(text: &str) -> BTreeSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+/// Parse a `--since`/`--until` bound: a unix timestamp or a `YYYY-MM-DD`
+/// date, taken as the start of that day (or its end when `end_of_day`)
+pub fn parse_time_bound(value: &str, end_of_day: bool) -> Result<u64> {
+    if let Ok(timestamp) = value.parse::<u64>() {
+        return Ok(timestamp);
+    }
+    let date = chrono::NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .map_err(|_| CliError::InvalidInput(format!("Invalid date {}: use YYYY-MM-DD or a unix timestamp", value)))?;
+    let time = if end_of_day {
+        date.and_hms_opt(23, 59, 59)
+    } else {
+        date.and_hms_opt(0, 0, 0)
+    };
+    Ok(time.map(|t| t.and_utc().timestamp() as u64).unwrap_or(0))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(id: &str, content: &str, created_at: u64) -> IndexedMessage {
+        IndexedMessage {
+            id: id.to_string(),
+            group_id: "g1".to_string(),
+            author: "aa".repeat(32),
+            content: content.to_string(),
+            created_at,
+            kind: 9,
+            is_deleted: false,
+        }
+    }
+
+    #[test]
+    fn test_search_words_regex_and_filters() {
+        let mut index = SearchIndex::new();
+        index.insert(message("1", "Release planning for Friday", 100));
+        index.insert(message("2", "Friday works, ship the release", 200));
+        index.insert(message("3", "lunch?", 300));
+
+        let query = |text: &str| SearchQuery { text: text.to_string(), limit: 10, ..Default::default() };
+        let ids = |hits: Vec<&IndexedMessage>| hits.iter().map(|m| m.id.clone()).collect::<Vec<_>>();
+
+        assert_eq!(ids(index.search(&query("release fri")).unwrap()), vec!["2", "1"]);
+        assert_eq!(ids(index.search(&query("lunch")).unwrap()), vec!["3"]);
+        assert!(index.search(&query("dinner")).unwrap().is_empty());
+
+        let regex = SearchQuery { regex: true, ..query(r"^friday\b") };
+        assert_eq!(ids(index.search(&regex).unwrap()), vec!["2"]);
+        assert!(index.search(&SearchQuery { regex: true, ..query("(") }).is_err());
+
+        let bounded = SearchQuery { since: Some(150), ..query("") };
+        assert_eq!(ids(index.search(&bounded).unwrap()), vec!["3", "2"]);
+
+        // Edits re-tokenize the stored message
+        index.insert(message("3", "dinner?", 300));
+        assert_eq!(ids(index.search(&query("dinner")).unwrap()), vec!["3"]);
+        assert!(index.search(&query("lunch")).unwrap().is_empty());
+
+        assert_eq!(parse_time_bound("1970-01-02", false).unwrap(), 86400);
+        assert!(parse_time_bound("yesterday", false).is_err());
+    }
+}
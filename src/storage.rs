@@ -1,20 +1,19 @@
 use anyhow::Result;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::{Path, PathBuf};
 
 use crate::contacts::ContactManager;
+use crate::export::ConversationArchive;
+use crate::search::SearchIndex;
 
 pub struct Storage {
     data_dir: PathBuf,
 }
 
 impl Storage {
-    pub async fn new() -> Result<Self> {
-        // Use current working directory for folder-based persistence
-        let data_dir = std::env::current_dir()?
-            .join(".whitenoise-cli");
-
+    /// Keep CLI state in `data_dir`, creating it if needed
+    pub fn open(data_dir: PathBuf) -> Result<Self> {
         std::fs::create_dir_all(&data_dir)?;
-
         Ok(Self { data_dir })
     }
 
@@ -32,7 +31,7 @@ impl Storage {
     pub async fn save_contacts(&self, contacts: &ContactManager) -> Result<()> {
         let path = self.data_dir.join("contacts.json");
         let json = serde_json::to_string_pretty(contacts)?;
-        std::fs::write(path, json)?;
+        write_file(&path, json)?;
         Ok(())
     }
 
@@ -49,7 +48,7 @@ impl Storage {
 
     pub async fn save_current_account_pubkey(&self, pubkey: &str) -> Result<()> {
         let path = self.data_dir.join("current_account_pubkey.txt");
-        std::fs::write(path, pubkey)?;
+        write_file(&path, pubkey)?;
         Ok(())
     }
 
@@ -70,4 +69,202 @@ impl Storage {
         }
         Ok(())
     }
-}
\ No newline at end of file
+
+    /// Group ids archived by an account; archiving is local to this machine
+    pub async fn load_archived_groups(&self, account_pubkey: &str) -> Result<BTreeSet<String>> {
+        Ok(self.read_archived_groups()?.remove(account_pubkey).unwrap_or_default())
+    }
+
+    pub async fn save_archived_groups(&self, account_pubkey: &str, group_ids: &BTreeSet<String>) -> Result<()> {
+        let mut archived = self.read_archived_groups()?;
+        if group_ids.is_empty() {
+            archived.remove(account_pubkey);
+        } else {
+            archived.insert(account_pubkey.to_string(), group_ids.clone());
+        }
+        let path = self.data_dir.join("archived_groups.json");
+        write_file(&path, serde_json::to_string_pretty(&archived)?)?;
+        Ok(())
+    }
+
+    fn read_archived_groups(&self) -> Result<BTreeMap<String, BTreeSet<String>>> {
+        let path = self.data_dir.join("archived_groups.json");
+        if !path.exists() {
+            return Ok(BTreeMap::new());
+        }
+
+        let json = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// Relay profile an account has chosen with `relay profile use`
+    pub async fn load_account_relay_profile(&self, account_pubkey: &str) -> Result<Option<String>> {
+        Ok(self.read_account_relay_profiles()?.remove(account_pubkey))
+    }
+
+    pub async fn save_account_relay_profile(&self, account_pubkey: &str, profile: Option<&str>) -> Result<()> {
+        let mut profiles = self.read_account_relay_profiles()?;
+        match profile {
+            Some(profile) => profiles.insert(account_pubkey.to_string(), profile.to_string()),
+            None => profiles.remove(account_pubkey),
+        };
+        let path = self.data_dir.join("relay_profiles.json");
+        write_file(&path, serde_json::to_string_pretty(&profiles)?)?;
+        Ok(())
+    }
+
+    fn read_account_relay_profiles(&self) -> Result<BTreeMap<String, String>> {
+        let path = self.data_dir.join("relay_profiles.json");
+        if !path.exists() {
+            return Ok(BTreeMap::new());
+        }
+
+        let json = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    /// Display names of local accounts, cached from their profile metadata
+    pub async fn load_account_names(&self) -> Result<BTreeMap<String, String>> {
+        let path = self.data_dir.join("account_names.json");
+        if !path.exists() {
+            return Ok(BTreeMap::new());
+        }
+
+        let json = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    pub async fn save_account_name(&self, account_pubkey: &str, name: &str) -> Result<()> {
+        let mut names = self.load_account_names().await?;
+        if names.get(account_pubkey).map(String::as_str) == Some(name) {
+            return Ok(());
+        }
+        names.insert(account_pubkey.to_string(), name.to_string());
+        let path = self.data_dir.join("account_names.json");
+        write_file(&path, serde_json::to_string_pretty(&names)?)?;
+        Ok(())
+    }
+
+    /// Group id -> `created_at` of the newest message the account has read
+    pub async fn load_read_markers(&self, account_pubkey: &str) -> Result<BTreeMap<String, u64>> {
+        Ok(self.read_read_markers()?.remove(account_pubkey).unwrap_or_default())
+    }
+
+    /// Move the account's read marker for a group forward to `read_at`
+    pub async fn mark_group_read(&self, account_pubkey: &str, group_id: &str, read_at: u64) -> Result<()> {
+        let mut markers = self.read_read_markers()?;
+        let marker = markers
+            .entry(account_pubkey.to_string())
+            .or_default()
+            .entry(group_id.to_string())
+            .or_default();
+        if *marker >= read_at {
+            return Ok(());
+        }
+        *marker = read_at;
+        let path = self.data_dir.join("read_markers.json");
+        write_file(&path, serde_json::to_string_pretty(&markers)?)?;
+        Ok(())
+    }
+
+    fn read_read_markers(&self) -> Result<BTreeMap<String, BTreeMap<String, u64>>> {
+        let path = self.data_dir.join("read_markers.json");
+        if !path.exists() {
+            return Ok(BTreeMap::new());
+        }
+
+        let json = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json)?)
+    }
+
+    pub async fn load_search_index(&self, account_pubkey: &str) -> Result<SearchIndex> {
+        let path = self.data_dir.join("search").join(format!("{}.json", account_pubkey));
+        if !path.exists() {
+            return Ok(SearchIndex::new());
+        }
+
+        // A corrupt index is only a cache; start over rather than fail the search
+        let json = std::fs::read_to_string(path)?;
+        Ok(serde_json::from_str(&json).map(SearchIndex::from_stored).unwrap_or_else(|_| SearchIndex::new()))
+    }
+
+    pub async fn save_search_index(&self, account_pubkey: &str, index: &SearchIndex) -> Result<()> {
+        let dir = self.data_dir.join("search");
+        std::fs::create_dir_all(&dir)?;
+        // Holds decrypted message text, hence owner-only like every file `write_file` creates
+        write_file(&dir.join(format!("{}.json", account_pubkey)), serde_json::to_string(index)?)?;
+        Ok(())
+    }
+
+    /// Keep a verified archive for read-only browsing; returns its id
+    pub async fn save_imported_archive(&self, archive: &ConversationArchive) -> Result<String> {
+        let dir = self.data_dir.join("imports");
+        std::fs::create_dir_all(&dir)?;
+        let id = archive.id();
+        write_file(&dir.join(format!("{}.json", id)), serde_json::to_string_pretty(archive)?)?;
+        Ok(id)
+    }
+
+    pub async fn load_imported_archives(&self) -> Result<Vec<ConversationArchive>> {
+        let dir = self.data_dir.join("imports");
+        if !dir.exists() {
+            return Ok(Vec::new());
+        }
+
+        let mut archives = Vec::new();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|ext| ext.to_str()) == Some("json") {
+                let json = std::fs::read_to_string(&path)?;
+                archives.push(serde_json::from_str(&json)?);
+            }
+        }
+        archives.sort_by_key(|archive: &ConversationArchive| archive.exported_at);
+        Ok(archives)
+    }
+}
+
+/// Replace `path` through a temporary file and a rename, so a crash or a
+/// concurrent run never leaves a truncated file. Files are owner-only.
+pub(crate) fn write_file(path: &Path, contents: impl AsRef<[u8]>) -> Result<()> {
+    use std::io::Write;
+
+    let tmp_path = path.with_extension(format!("tmp.{}", std::process::id()));
+    let mut options = std::fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+
+    let mut file = options.open(&tmp_path)?;
+    file.write_all(contents.as_ref())?;
+    file.sync_all()?;
+    std::fs::rename(&tmp_path, path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_read_markers_only_move_forward() {
+        let dir = std::env::temp_dir().join(format!("whitenoise_storage_{}", uuid::Uuid::new_v4()));
+        let storage = Storage::open(dir.clone()).unwrap();
+
+        storage.mark_group_read("alice", "g1", 200).await.unwrap();
+        storage.mark_group_read("alice", "g1", 100).await.unwrap();
+        storage.mark_group_read("alice", "g2", 50).await.unwrap();
+        storage.mark_group_read("bob", "g1", 300).await.unwrap();
+
+        let markers = storage.load_read_markers("alice").await.unwrap();
+        assert_eq!(markers.get("g1"), Some(&200));
+        assert_eq!(markers.get("g2"), Some(&50));
+        assert_eq!(storage.load_read_markers("bob").await.unwrap().get("g1"), Some(&300));
+        assert!(storage.load_read_markers("carol").await.unwrap().is_empty());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,337 @@
+//! In-process Nostr relay for tests. It speaks enough of NIP-01 for the
+//! WhiteNoise client (EVENT/OK, REQ/EOSE with live delivery, CLOSE, COUNT),
+//! serves a NIP-11 document over plain HTTP and keeps everything in memory,
+//! so integration tests never leave the host.
+
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+
+pub struct MockRelay {
+    url: String,
+    state: Arc<Mutex<RelayState>>,
+    task: JoinHandle<()>,
+}
+
+impl MockRelay {
+    /// Listen on a free loopback port until the relay is dropped
+    pub async fn start() -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind mock relay");
+        let url = format!("ws://{}", listener.local_addr().expect("mock relay address"));
+        let state = Arc::new(Mutex::new(RelayState::default()));
+
+        let accept_state = state.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve(stream, accept_state.clone()));
+            }
+        });
+
+        Self { url, state, task }
+    }
+
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Every stored event, in the order received
+    pub fn events(&self) -> Vec<Value> {
+        self.state.lock().unwrap().events.clone()
+    }
+
+    pub fn events_of_kind(&self, kind: u64) -> Vec<Value> {
+        self.events().into_iter().filter(|e| e["kind"].as_u64() == Some(kind)).collect()
+    }
+}
+
+impl Drop for MockRelay {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+#[derive(Default)]
+struct RelayState {
+    events: Vec<Value>,
+    connections: HashMap<usize, Connection>,
+    next_connection: usize,
+}
+
+struct Connection {
+    outbox: mpsc::UnboundedSender<Value>,
+    subscriptions: HashMap<String, Vec<Value>>,
+}
+
+async fn serve(mut stream: TcpStream, state: Arc<Mutex<RelayState>>) {
+    // Peek at the request head: NIP-11 documents go to plain HTTP requests
+    let mut head = [0u8; 2048];
+    let upgrade = loop {
+        let n = match stream.peek(&mut head).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => n,
+        };
+        let text = String::from_utf8_lossy(&head[..n]).to_lowercase();
+        if text.contains("\r\n\r\n") || n == head.len() {
+            break text.contains("upgrade: websocket");
+        }
+    };
+    if !upgrade {
+        let _ = stream.read(&mut head).await;
+        let body = json!({
+            "name": "mock relay",
+            "supported_nips": [1, 11, 45],
+            "limitation": { "max_subscriptions": 20, "auth_required": false }
+        })
+        .to_string();
+        let response = format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: application/nostr+json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            body.len(),
+            body
+        );
+        let _ = stream.write_all(response.as_bytes()).await;
+        return;
+    }
+
+    let Ok(socket) = tokio_tungstenite::accept_async(stream).await else { return };
+    let (mut sink, mut incoming) = socket.split();
+    let (outbox, mut queued) = mpsc::unbounded_channel::<Value>();
+
+    let id = {
+        let mut state = state.lock().unwrap();
+        state.next_connection += 1;
+        let id = state.next_connection;
+        state.connections.insert(id, Connection { outbox, subscriptions: HashMap::new() });
+        id
+    };
+
+    let writer = tokio::spawn(async move {
+        while let Some(message) = queued.recv().await {
+            if sink.send(Message::Text(message.to_string().into())).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    while let Some(Ok(message)) = incoming.next().await {
+        match message {
+            Message::Text(text) => state.lock().unwrap().handle(id, text.as_str()),
+            Message::Close(_) => break,
+            _ => {}
+        }
+    }
+
+    state.lock().unwrap().connections.remove(&id);
+    writer.abort();
+}
+
+impl RelayState {
+    fn handle(&mut self, connection: usize, text: &str) {
+        let message: Vec<Value> = match serde_json::from_str(text) {
+            Ok(message) => message,
+            Err(e) => return self.send(connection, json!(["NOTICE", format!("invalid message: {}", e)])),
+        };
+        let subscription = message.get(1).and_then(Value::as_str).unwrap_or_default().to_string();
+
+        match message.first().and_then(Value::as_str) {
+            Some("EVENT") => {
+                let event = message.get(1).cloned().unwrap_or_default();
+                let reply = self.publish(event.clone());
+                self.send(connection, json!(["OK", event["id"], reply.is_ok(), reply.unwrap_or_else(|e| e)]));
+            }
+            Some("REQ") => {
+                let filters = message[2..].to_vec();
+                for event in self.query(&filters) {
+                    self.send(connection, json!(["EVENT", subscription, event]));
+                }
+                self.send(connection, json!(["EOSE", subscription]));
+                if let Some(conn) = self.connections.get_mut(&connection) {
+                    conn.subscriptions.insert(subscription, filters);
+                }
+            }
+            Some("CLOSE") => {
+                if let Some(conn) = self.connections.get_mut(&connection) {
+                    conn.subscriptions.remove(&subscription);
+                }
+            }
+            Some("COUNT") => {
+                let count = self.query(&message[2..]).len();
+                self.send(connection, json!(["COUNT", subscription, { "count": count }]));
+            }
+            // Negentropy sync is optional; clients fall back to REQ
+            Some("NEG-OPEN") => self.send(connection, json!(["NEG-ERR", subscription, "unsupported"])),
+            _ => self.send(connection, json!(["NOTICE", "unsupported message"])),
+        }
+    }
+
+    /// Store an event and deliver it to matching subscriptions. The `Ok`
+    /// and `Err` strings are the OK message's NIP-01 prefix and reason.
+    fn publish(&mut self, event: Value) -> Result<String, String> {
+        let valid = event["id"].as_str().is_some_and(|id| id.len() == 64)
+            && event["pubkey"].as_str().is_some_and(|pk| pk.len() == 64)
+            && event["kind"].is_u64()
+            && event["created_at"].is_u64()
+            && event["tags"].is_array()
+            && event["content"].is_string()
+            && event["sig"].is_string();
+        if !valid {
+            return Err("invalid: malformed event".to_string());
+        }
+        if self.events.iter().any(|e| e["id"] == event["id"]) {
+            return Ok("duplicate: already have this event".to_string());
+        }
+
+        let kind = event["kind"].as_u64().unwrap_or_default();
+        let replaces = |old: &Value| {
+            let same_author = old["pubkey"] == event["pubkey"] && old["kind"] == event["kind"];
+            match kind {
+                0 | 3 | 10000..=19999 => same_author,
+                30000..=39999 => same_author && tag_value(old, "d") == tag_value(&event, "d"),
+                _ => false,
+            }
+        };
+        if self.events.iter().any(|old| replaces(old) && old["created_at"].as_u64() > event["created_at"].as_u64()) {
+            return Ok("duplicate: a newer version is stored".to_string());
+        }
+        // Ephemeral events are delivered but not kept
+        if !(20000..30000).contains(&kind) {
+            self.events.retain(|old| !replaces(old));
+            self.events.push(event.clone());
+        }
+
+        for conn in self.connections.values() {
+            for (subscription, filters) in &conn.subscriptions {
+                if filters.iter().any(|filter| matches_filter(filter, &event)) {
+                    let _ = conn.outbox.send(json!(["EVENT", subscription, event]));
+                }
+            }
+        }
+        Ok(String::new())
+    }
+
+    /// Stored events matching any filter, newest first, each filter's
+    /// `limit` applied separately
+    fn query(&self, filters: &[Value]) -> Vec<Value> {
+        let mut newest_first: Vec<&Value> = self.events.iter().collect();
+        newest_first.sort_by_key(|e| std::cmp::Reverse(e["created_at"].as_u64()));
+
+        let mut found: Vec<Value> = Vec::new();
+        for filter in filters {
+            let limit = filter["limit"].as_u64().map_or(usize::MAX, |l| l as usize);
+            for event in newest_first.iter().filter(|e| matches_filter(filter, e)).take(limit) {
+                if !found.iter().any(|f| f["id"] == event["id"]) {
+                    found.push((*event).clone());
+                }
+            }
+        }
+        found
+    }
+
+    fn send(&self, connection: usize, message: Value) {
+        if let Some(conn) = self.connections.get(&connection) {
+            let _ = conn.outbox.send(message);
+        }
+    }
+}
+
+fn tag_value<'a>(event: &'a Value, name: &str) -> Option<&'a str> {
+    event["tags"].as_array()?.iter()
+        .find(|tag| tag[0].as_str() == Some(name))
+        .map(|tag| tag[1].as_str().unwrap_or_default())
+}
+
+/// NIP-01 filter matching: ids and authors by prefix, kinds, since/until
+/// and `#x` single-letter tag queries
+fn matches_filter(filter: &Value, event: &Value) -> bool {
+    let Some(filter) = filter.as_object() else { return false };
+    let any_of = |key: &str, test: &dyn Fn(&Value) -> bool| {
+        filter.get(key).and_then(Value::as_array).is_none_or(|values| values.iter().any(test))
+    };
+    let prefix_of = |field: &str| {
+        let value = event[field].as_str().unwrap_or_default().to_string();
+        move |prefix: &Value| prefix.as_str().is_some_and(|p| value.starts_with(p))
+    };
+    let created_at = event["created_at"].as_u64().unwrap_or_default();
+
+    any_of("ids", &prefix_of("id"))
+        && any_of("authors", &prefix_of("pubkey"))
+        && any_of("kinds", &|kind| *kind == event["kind"])
+        && filter.get("since").and_then(Value::as_u64).is_none_or(|since| created_at >= since)
+        && filter.get("until").and_then(Value::as_u64).is_none_or(|until| created_at <= until)
+        && filter.iter()
+            .filter_map(|(key, values)| Some((key.strip_prefix('#')?, values.as_array()?)))
+            .all(|(name, values)| {
+                event["tags"].as_array().is_some_and(|tags| tags.iter().any(|tag| {
+                    tag[0].as_str() == Some(name) && values.contains(&tag[1])
+                }))
+            })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+    type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    fn event(id: char, kind: u64, created_at: u64, tags: Value) -> Value {
+        json!({
+            "id": id.to_string().repeat(64),
+            "pubkey": "a".repeat(64),
+            "kind": kind,
+            "created_at": created_at,
+            "tags": tags,
+            "content": format!("event {}", id),
+            "sig": "0".repeat(128),
+        })
+    }
+
+    async fn send(client: &mut Client, message: Value) {
+        client.send(Message::Text(message.to_string().into())).await.unwrap();
+    }
+
+    async fn recv(client: &mut Client) -> Value {
+        loop {
+            if let Message::Text(text) = client.next().await.unwrap().unwrap() {
+                return serde_json::from_str(text.as_str()).unwrap();
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_publish_query_and_live_delivery() {
+        let relay = MockRelay::start().await;
+        let (mut reader, _) = connect_async(relay.url()).await.unwrap();
+        let (mut writer, _) = connect_async(relay.url()).await.unwrap();
+
+        send(&mut reader, json!(["REQ", "wraps", { "kinds": [1059], "#p": ["b".repeat(64)] }])).await;
+        assert_eq!(recv(&mut reader).await, json!(["EOSE", "wraps"]));
+
+        let wrap = event('1', 1059, 100, json!([["p", "b".repeat(64)]]));
+        send(&mut writer, json!(["EVENT", wrap])).await;
+        assert_eq!(recv(&mut writer).await, json!(["OK", "1".repeat(64), true, ""]));
+        assert_eq!(recv(&mut reader).await, json!(["EVENT", "wraps", wrap]));
+
+        // Kind 0 is replaceable; only the newest metadata is served
+        send(&mut writer, json!(["EVENT", event('2', 0, 100, json!([]))])).await;
+        send(&mut writer, json!(["EVENT", event('3', 0, 200, json!([]))])).await;
+        send(&mut writer, json!(["EVENT", event('4', 1, 300, json!([]))])).await;
+        for _ in 0..3 {
+            assert_eq!(recv(&mut writer).await[2], true);
+        }
+        send(&mut writer, json!(["EVENT", { "id": "bad" }])).await;
+        assert_eq!(recv(&mut writer).await[2], false);
+
+        send(&mut writer, json!(["REQ", "meta", { "kinds": [0, 1], "limit": 1 }, { "ids": ["3"] }])).await;
+        assert_eq!(recv(&mut writer).await[2]["id"], "4".repeat(64));
+        assert_eq!(recv(&mut writer).await[2]["id"], "3".repeat(64));
+        assert_eq!(recv(&mut writer).await, json!(["EOSE", "meta"]));
+        assert_eq!(relay.events_of_kind(0).len(), 1);
+        assert_eq!(relay.events().len(), 3);
+    }
+}
@@ -1,23 +1,17 @@
 use anyhow::Result;
-use std::path::PathBuf;
 use whitenoise::{Whitenoise, WhitenoiseConfig};
 
+use crate::config::Config;
+
 pub struct WhitenoiseManager {
     config: WhitenoiseConfig,
     initialized: bool,
 }
 
 impl WhitenoiseManager {
-    pub fn new() -> Result<Self> {
-        let data_dir = dirs::data_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join("whitenoise-cli")
-            .join("data");
-
-        let logs_dir = dirs::data_dir()
-            .unwrap_or_else(|| PathBuf::from("."))
-            .join("whitenoise-cli")
-            .join("logs");
+    pub fn new(cli_config: &Config) -> Result<Self> {
+        let data_dir = cli_config.data_dir();
+        let logs_dir = cli_config.logs_dir();
 
         // Create directories if they don't exist
         std::fs::create_dir_all(&data_dir)?;